@@ -1,4 +1,14 @@
-use std::ops::{Index, IndexMut, Deref, DerefMut};
+use std::ops::{Index, IndexMut, Deref, DerefMut, BitOr, Range};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::cmp;
+use std::mem;
+
+use unicode_width::UnicodeWidthChar;
 
 use core::position::{Pos, Size, HasSize};
 
@@ -71,285 +81,4508 @@ pub trait CellAccessor: HasSize {
 ///
 /// The first index, `Cellbuffer[y]`, corresponds to a row, and thus the y-axis. The second
 /// index, `Cellbuffer[y][x]`, corresponds to a column within a row and thus the x-axis.
+///
+/// Two `CellBuffer`s are equal if they have the same size and cells; their version history and
+/// checkpoints are not compared.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CellBuffer {
     cols: usize,
     rows: usize,
     buf: Vec<Cell>,
+    versions: Versions,
+    history: History,
 }
 
-impl CellBuffer {
-    /// Constructs a new `CellBuffer` with the given number of columns and rows, using the given
-    /// `cell` as a blank.
-    pub fn new(cols: usize, rows: usize, cell: Cell) -> CellBuffer {
-        CellBuffer {
-            cols: cols,
-            rows: rows,
-            buf: vec![cell; cols * rows],
+/// The number of checkpoints a `CellBuffer` keeps unless told otherwise with
+/// `CellBuffer::set_history_depth`.
+const DEFAULT_HISTORY_DEPTH: usize = 8;
+
+/// Checkpoints saved by `CellBuffer::checkpoint`, oldest first.
+///
+/// The history is an undo stack for one buffer, so a clone of the buffer starts out with an empty
+/// history (but the same depth), and histories are not compared.
+#[derive(Debug)]
+struct History {
+    depth: usize,
+    snapshots: VecDeque<(Size, Vec<Cell>)>,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History {
+            depth: DEFAULT_HISTORY_DEPTH,
+            snapshots: VecDeque::new(),
         }
     }
+}
 
-    /// Resizes `CellBuffer` to the given number of rows and columns, using the given `Cell` as
-    /// a blank.
-    pub fn resize(&mut self, newcols: usize, newrows: usize, blank: Cell) {
-        let newlen = newcols * newrows;
-        let mut newbuf: Vec<Cell> = Vec::with_capacity(newlen);
-        for y in 0..newrows {
-            for x in 0..newcols {
-                let cell = self.get(x, y).unwrap_or(&blank);
-                newbuf.push(*cell);
-            }
+impl Clone for History {
+    fn clone(&self) -> History {
+        History {
+            depth: self.depth,
+            snapshots: VecDeque::new(),
         }
-        self.buf = newbuf;
-        self.cols = newcols;
-        self.rows = newrows;
     }
 }
 
-impl HasSize for CellBuffer {
-    fn size(&self) -> Size {
-        (self.cols, self.rows)
+impl PartialEq for History {
+    fn eq(&self, _: &History) -> bool {
+        true
     }
 }
 
-impl CellAccessor for CellBuffer {
-    fn cellvec(&self) -> &Vec<Cell> {
-        &self.buf
-    }
+impl Eq for History {}
 
-    fn cellvec_mut(&mut self) -> &mut Vec<Cell> {
-        &mut self.buf
-    }
+/// Write stamps, used by `CellBuffer::version` and `CellBuffer::changed_since`.
+///
+/// Every write starts a new version. Writes to the whole buffer only record that version in
+/// `all`; per-cell stamps are kept only once enabled with `CellBuffer::set_version_tracking`.
+/// Stamps only record when cells were written, so they are not compared.
+#[derive(Debug, Clone, Default)]
+struct Versions {
+    current: u64,
+    // The version of the last write to every cell at once.
+    all: u64,
+    stamps: Option<Vec<u64>>,
 }
 
-impl Deref for CellBuffer {
-    type Target = [Cell];
+impl Versions {
+    /// Starts a new version and returns it.
+    fn bump(&mut self) -> u64 {
+        self.current += 1;
+        self.current
+    }
 
-    fn deref<'a>(&'a self) -> &'a [Cell] {
-        &self.buf
+    /// Stamps the cell at offset `i` with a new version.
+    fn touch(&mut self, i: usize) {
+        let v = self.bump();
+        if let Some(ref mut stamps) = self.stamps {
+            stamps[i] = v;
+        }
     }
-}
 
-impl DerefMut for CellBuffer {
-    fn deref_mut<'a>(&'a mut self) -> &'a mut [Cell] {
-        &mut self.buf
+    /// Stamps the cells at the offsets in `range` with a new version.
+    fn touch_range(&mut self, range: Range<usize>) {
+        let v = self.bump();
+        if let Some(ref mut stamps) = self.stamps {
+            for stamp in &mut stamps[range] {
+                *stamp = v;
+            }
+        }
     }
-}
 
-impl Index<Pos> for CellBuffer {
-    type Output = Cell;
+    /// Stamps all `len` cells with a new version, growing or shrinking the stamps to match.
+    fn touch_all(&mut self, len: usize) {
+        self.all = self.bump();
+        if let Some(ref mut stamps) = self.stamps {
+            // Stamps left over from before are all older than `all`.
+            stamps.resize(len, 0);
+        }
+    }
 
-    fn index<'a>(&'a self, index: Pos) -> &'a Cell {
-        let (x, y) = index;
-        self.get(x, y).expect("index out of bounds")
+    /// Returns the version of the last write to the cell at offset `i`, or the current version
+    /// if cells aren't being tracked.
+    fn stamp(&self, i: usize) -> u64 {
+        match self.stamps {
+            Some(ref stamps) => cmp::max(stamps[i], self.all),
+            None => self.current,
+        }
     }
 }
 
-impl IndexMut<Pos> for CellBuffer {
-    fn index_mut<'a>(&'a mut self, index: Pos) -> &'a mut Cell {
-        let (x, y) = index;
-        self.get_mut(x, y).expect("index out of bounds")
+impl PartialEq for Versions {
+    fn eq(&self, _: &Versions) -> bool {
+        true
     }
 }
 
-impl Default for CellBuffer {
-    /// Constructs a new `CellBuffer` with a size of `(0, 0)`, using the default `Cell` as a blank.
-    fn default() -> CellBuffer {
-        CellBuffer::new(0, 0, Cell::default())
+impl Eq for Versions {}
+
+impl CellBuffer {
+    /// Constructs a new `CellBuffer` with the given number of columns and rows, using the given
+    /// `cell` as a blank.
+    pub fn new(cols: usize, rows: usize, cell: Cell) -> CellBuffer {
+        CellBuffer::from_vec(cols, rows, vec![cell; cols * rows])
     }
-}
 
-/// A single point on a terminal display.
-///
-/// A `Cell` contains a character and style.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct Cell {
-    ch: char,
-    fg: Color,
-    bg: Color,
-    attrs: Attr,
-}
+    /// Constructs a new `CellBuffer` from a row-major `Vec` of `cols * rows` cells.
+    fn from_vec(cols: usize, rows: usize, buf: Vec<Cell>) -> CellBuffer {
+        CellBuffer {
+            cols,
+            rows,
+            buf,
+            versions: Versions::default(),
+            history: History::default(),
+        }
+    }
 
-impl Cell {
-    /// Creates a new `Cell` with the given `char`, `Color`s and `Attr`.
+    /// Stamps every cell with a new version, for operations that rewrite the whole buffer.
+    fn touch_all(&mut self) {
+        self.versions.touch_all(self.buf.len());
+    }
+
+    /// Resizes `CellBuffer` to the given number of rows and columns, using the given `Cell` as
+    /// a blank.
+    ///
+    /// The cells are rearranged in place, so no reallocation happens as long as the backing
+    /// storage has enough capacity (see `with_reserved` and `reserve`).
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color, Attr};
+    /// use rustty::{CellBuffer, Cell};
     ///
-    /// let cell = Cell::new('x', Color::Default, Color::Green, Attr::Default);
-    /// assert_eq!(cell.ch(), 'x');
-    /// assert_eq!(cell.fg(), Color::Default);
-    /// assert_eq!(cell.bg(), Color::Green);
-    /// assert_eq!(cell.attrs(), Attr::Default);
+    /// let mut buf = CellBuffer::new(3, 2, Cell::default());
+    /// for (i, cell) in buf.iter_mut().enumerate() {
+    ///     cell.set_ch((b'a' + i as u8) as char);
+    /// }
+    /// buf.resize(4, 3, Cell::with_char('.'));
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "abc.def.....");
+    ///
+    /// buf.resize(2, 1, Cell::default());
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "ab");
     /// ```
-    pub fn new(ch: char, fg: Color, bg: Color, attrs: Attr) -> Cell {
-        Cell {
-            ch: ch,
-            fg: fg,
-            bg: bg,
-            attrs: attrs,
+    pub fn resize(&mut self, newcols: usize, newrows: usize, blank: Cell) {
+        let (cols, rows) = (self.cols, self.rows);
+        let newlen = newcols * newrows;
+        if newlen > self.buf.len() {
+            self.buf.resize(newlen, blank);
+        }
+        // Each cell only ever moves towards the front when rows get narrower and towards the back
+        // when they get wider, so walking in that direction never overwrites an unread cell.
+        let remap = |buf: &mut Vec<Cell>, x: usize, y: usize| {
+            buf[y * newcols + x] = if x < cols && y < rows {
+                buf[y * cols + x]
+            } else {
+                blank
+            };
+        };
+        if newcols <= cols {
+            for y in 0..newrows {
+                for x in 0..newcols {
+                    remap(&mut self.buf, x, y);
+                }
+            }
+        } else {
+            for y in (0..newrows).rev() {
+                for x in (0..newcols).rev() {
+                    remap(&mut self.buf, x, y);
+                }
+            }
         }
+        self.buf.truncate(newlen);
+        self.touch_all();
+        self.cols = newcols;
+        self.rows = newrows;
     }
 
-    /// Creates a new `Cell` with the given `char` and default style.
+    /// Constructs a blank `cols`×`rows` buffer whose backing storage has room for
+    /// `reserve_cols`×`reserve_rows` cells, so it can later be grown to that size with `resize`
+    /// without reallocating.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color, Attr};
+    /// use rustty::{CellBuffer, Cell, HasSize};
     ///
-    /// let mut cell = Cell::with_char('x');
-    /// assert_eq!(cell.ch(), 'x');
-    /// assert_eq!(cell.fg(), Color::Default);
-    /// assert_eq!(cell.bg(), Color::Default);
-    /// assert_eq!(cell.attrs(), Attr::Default);
+    /// let mut buf = CellBuffer::with_reserved(10, 5, 80, 24);
+    /// assert_eq!(buf.size(), (10, 5));
+    /// assert!(buf.capacity() >= 80 * 24);
+    ///
+    /// let capacity = buf.capacity();
+    /// buf.resize(80, 24, Cell::default());
+    /// assert_eq!(buf.capacity(), capacity);
     /// ```
-    pub fn with_char(ch: char) -> Cell {
-        Cell::new(ch, Color::Default, Color::Default, Attr::Default)
+    pub fn with_reserved(cols: usize,
+                         rows: usize,
+                         reserve_cols: usize,
+                         reserve_rows: usize)
+                         -> CellBuffer {
+        let mut buf = Vec::with_capacity(cmp::max(cols * rows, reserve_cols * reserve_rows));
+        buf.resize(cols * rows, Cell::default());
+        CellBuffer::from_vec(cols, rows, buf)
     }
 
-    /// Creates a new `Cell` with the given style and a blank `char`.
+    /// Reserves backing storage for at least `extra_cells` more cells than the buffer currently
+    /// holds.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color, Attr};
+    /// use rustty::{CellBuffer, Cell};
     ///
-    /// let mut cell = Cell::with_style(Color::Default, Color::Red, Attr::Bold);
-    /// assert_eq!(cell.fg(), Color::Default);
-    /// assert_eq!(cell.bg(), Color::Red);
-    /// assert_eq!(cell.attrs(), Attr::Bold);
-    /// assert_eq!(cell.ch(), ' ');
+    /// let mut buf = CellBuffer::new(4, 4, Cell::default());
+    /// buf.reserve(16);
+    /// assert!(buf.capacity() >= 32);
     /// ```
-    pub fn with_style(fg: Color, bg: Color, attr: Attr) -> Cell {
-        Cell::new(' ', fg, bg, attr)
+    pub fn reserve(&mut self, extra_cells: usize) {
+        self.buf.reserve(extra_cells);
+        if let Some(ref mut stamps) = self.versions.stamps {
+            stamps.reserve(extra_cells);
+        }
     }
 
-    /// Returns the `Cell`'s character.
+    /// Returns the number of cells the backing storage can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Returns a reduced copy of the buffer in which each `factor`×`factor` block of cells is
+    /// merged into a single cell.
+    ///
+    /// The background colors of each block are averaged in RGB (`Color::Default` cells are
+    /// ignored), and the most common character in the block is kept along with its style. The
+    /// result is `ceil(cols / factor)` columns by `ceil(rows / factor)` rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is zero.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::Cell;
+    /// use rustty::{CellBuffer, Cell, Color, Attr, HasSize};
     ///
-    /// let mut cell = Cell::with_char('x');
-    /// assert_eq!(cell.ch(), 'x');
+    /// let mut buf = CellBuffer::new(4, 4, Cell::default());
+    /// buf[(0, 0)] = Cell::new('a', Color::Default, Color::Rgb(0, 0, 0), Attr::Default);
+    /// buf[(1, 0)] = Cell::new('a', Color::Default, Color::Rgb(100, 0, 0), Attr::Default);
+    /// buf[(0, 1)] = Cell::new('b', Color::Default, Color::Rgb(0, 200, 0), Attr::Default);
+    /// buf[(1, 1)] = Cell::new('a', Color::Default, Color::Rgb(0, 0, 40), Attr::Default);
+    ///
+    /// let small = buf.downsample(2);
+    /// assert_eq!(small.size(), (2, 2));
+    /// assert_eq!(small[(0, 0)].ch(), 'a');
+    /// assert_eq!(small[(0, 0)].bg(), Color::Rgb(25, 50, 10));
+    /// assert_eq!(small[(1, 1)], Cell::default());
+    ///
+    /// // A uniform buffer keeps its appearance.
+    /// let uniform = CellBuffer::new(3, 3, Cell::new('x', Color::Red, Color::Blue, Attr::Bold));
+    /// let small = uniform.downsample(2);
+    /// assert_eq!(small.size(), (2, 2));
+    /// assert!(small.iter().all(|c| *c == uniform[(0, 0)]));
     /// ```
-    pub fn ch(&self) -> char {
-        self.ch
+    pub fn downsample(&self, factor: usize) -> CellBuffer {
+        assert!(factor > 0, "downsample factor must be non-zero");
+        let newcols = self.cols.div_ceil(factor);
+        let newrows = self.rows.div_ceil(factor);
+        let mut newbuf = Vec::with_capacity(newcols * newrows);
+        for by in 0..newrows {
+            for bx in 0..newcols {
+                let block: Vec<&Cell> = (by * factor..self.rows.min((by + 1) * factor))
+                    .flat_map(|y| {
+                        (bx * factor..self.cols.min((bx + 1) * factor)).map(move |x| (x, y))
+                    })
+                    .map(|(x, y)| &self[(x, y)])
+                    .collect();
+
+                // The first cell holding the most common character represents the block.
+                let mut rep = block[0];
+                let mut best = 0;
+                for cell in block.iter() {
+                    let n = block.iter().filter(|c| c.ch() == cell.ch()).count();
+                    if n > best {
+                        best = n;
+                        rep = cell;
+                    }
+                }
+
+                let mut cell = *rep;
+                cell.set_bg(average_color(block.iter().map(|c| c.bg())));
+                newbuf.push(cell);
+            }
+        }
+        CellBuffer::from_vec(newcols, newrows, newbuf)
     }
 
-    /// Sets the `Cell`'s character to the given `char`
+    /// Inserts a column of `blank` cells at column `x`, shifting the columns to its right one place
+    /// further right. The last column is dropped so the size of the buffer is unchanged.
+    ///
+    /// Does nothing if `x` is out of bounds.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::Cell;
+    /// use rustty::{CellBuffer, Cell};
     ///
-    /// let mut cell = Cell::with_char('x');
-    /// assert_eq!(cell.ch(), 'x');
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)].set_ch('a');
+    /// buf[(1, 0)].set_ch('b');
+    /// buf[(2, 0)].set_ch('c');
     ///
-    /// cell.set_ch('y');
-    /// assert_eq!(cell.ch(), 'y');
+    /// buf.insert_col(0, Cell::with_char('-'));
+    /// let row: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(row, "-ab");
     /// ```
-    pub fn set_ch(&mut self, newch: char) -> &mut Cell {
-        self.ch = newch;
-        self
+    pub fn insert_col(&mut self, x: usize, blank: Cell) {
+        if x >= self.cols {
+            return;
+        }
+        self.touch_all();
+        for row in self.buf.chunks_mut(self.cols) {
+            row[x..].rotate_right(1);
+            row[x] = blank;
+        }
     }
 
-    /// Returns the `Cell`'s foreground `Color`.
+    /// Deletes the column at `x`, shifting the columns to its right one place left. The last
+    /// column is filled with `blank` so the size of the buffer is unchanged.
+    ///
+    /// Does nothing if `x` is out of bounds.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color, Attr};
+    /// use rustty::{CellBuffer, Cell};
     ///
-    /// let mut cell = Cell::with_style(Color::Blue, Color::Default, Attr::Default);
-    /// assert_eq!(cell.fg(), Color::Blue);
+    /// let mut buf = CellBuffer::new(3, 2, Cell::default());
+    /// for y in 0..2 {
+    ///     buf[(0, y)].set_ch('a');
+    ///     buf[(1, y)].set_ch('b');
+    ///     buf[(2, y)].set_ch('c');
+    /// }
+    ///
+    /// buf.delete_col(1, Cell::with_char('-'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "ac-ac-");
     /// ```
-    pub fn fg(&self) -> Color {
-        self.fg
+    pub fn delete_col(&mut self, x: usize, blank: Cell) {
+        if x >= self.cols {
+            return;
+        }
+        let last = self.cols - 1;
+        self.touch_all();
+        for row in self.buf.chunks_mut(self.cols) {
+            row[x..].rotate_left(1);
+            row[last] = blank;
+        }
     }
 
-    /// Sets the `Cell`'s foreground `Color` to the given `Color`.
+    /// Restyles the border of the rectangle with its top-left corner at `(x, y)` and a size of
+    /// `w`×`h`, leaving the characters and the interior of the rectangle untouched.
+    ///
+    /// Parts of the border lying outside the buffer are ignored.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color, Attr};
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
     ///
-    /// let mut cell = Cell::default();
-    /// assert_eq!(cell.fg(), Color::Default);
+    /// let mut buf = CellBuffer::new(5, 5, Cell::with_char('x'));
+    /// buf.outline_region(1, 1, 3, 3, Color::Default, Color::Default, Attr::Reverse);
     ///
-    /// cell.set_fg(Color::White);
-    /// assert_eq!(cell.fg(), Color::White);
+    /// assert_eq!(buf[(1, 1)].attrs(), Attr::Reverse);
+    /// assert_eq!(buf[(3, 2)].attrs(), Attr::Reverse);
+    /// assert_eq!(buf[(2, 3)].attrs(), Attr::Reverse);
+    /// assert_eq!(buf[(2, 2)].attrs(), Attr::Default);
+    /// assert_eq!(buf[(0, 0)].attrs(), Attr::Default);
+    /// assert!(buf.iter().all(|c| c.ch() == 'x'));
+    ///
+    /// // Only the top and left edges of an oversized rectangle are inside the buffer.
+    /// buf.outline_region(1, 1, usize::MAX, usize::MAX, Color::Red, Color::Default, Attr::Default);
+    /// assert_eq!(buf[(4, 1)].fg(), Color::Red);
+    /// assert_eq!(buf[(1, 4)].fg(), Color::Red);
+    /// assert_eq!(buf[(4, 4)].fg(), Color::Default);
     /// ```
-    pub fn set_fg(&mut self, newfg: Color) -> &mut Cell {
-        self.fg = newfg;
-        self
+    #[allow(clippy::too_many_arguments)]
+    pub fn outline_region(&mut self,
+                          x: usize,
+                          y: usize,
+                          w: usize,
+                          h: usize,
+                          fg: Color,
+                          bg: Color,
+                          attrs: Attr) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        // Edges beyond the end of the buffer saturate, which still keeps them out of reach.
+        let (right, bottom) = (x.saturating_add(w - 1), y.saturating_add(h - 1));
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        for iy in y..y + h {
+            for ix in x..x + w {
+                if ix == x || ix == right || iy == y || iy == bottom {
+                    let cell = &mut self[(ix, iy)];
+                    cell.set_fg(fg);
+                    cell.set_bg(bg);
+                    cell.set_attrs(attrs);
+                }
+            }
+        }
     }
 
-    /// Returns the `Cell`'s background `Color`.
+    /// Rotates the hue of every cell's foreground and background colors by `degrees`.
+    ///
+    /// Colors are converted to HSL, rotated, and stored back as `Color::Rgb`. `Color::Default` is
+    /// left untouched.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color, Attr};
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
     ///
-    /// let mut cell = Cell::with_style(Color::Default, Color::Green, Attr::Default);
-    /// assert_eq!(cell.bg(), Color::Green);
+    /// let mut buf = CellBuffer::new(2, 1, Cell::default());
+    /// buf[(0, 0)] = Cell::with_style(Color::Rgb(255, 0, 0), Color::Default, Attr::Default);
+    ///
+    /// buf.rotate_hue(120.0);
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Rgb(0, 255, 0));
+    /// assert_eq!(buf[(0, 0)].bg(), Color::Default);
+    /// assert_eq!(buf[(1, 0)], Cell::default());
     /// ```
-    pub fn bg(&self) -> Color {
-        self.bg
+    pub fn rotate_hue(&mut self, degrees: f32) {
+        let rotate = |color: Color| {
+            match color.to_rgb() {
+                Some(rgb) => {
+                    let (h, s, l) = rgb_to_hsl(rgb);
+                    let (r, g, b) = hsl_to_rgb(((h + degrees) % 360.0 + 360.0) % 360.0, s, l);
+                    Color::Rgb(r, g, b)
+                }
+                None => color,
+            }
+        };
+        self.touch_all();
+        for cell in self.buf.iter_mut() {
+            let (fg, bg) = (rotate(cell.fg()), rotate(cell.bg()));
+            cell.set_fg(fg);
+            cell.set_bg(bg);
+        }
     }
 
-    /// Sets the `Cell`'s background `Color` to the given `Color`.
+    /// Writes `s` downwards from `(x, y)`, one character per row, using the given style.
+    ///
+    /// Control characters are skipped and writing stops at the bottom edge of the buffer. Returns
+    /// the number of cells written.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color, Attr};
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
     ///
-    /// let mut cell = Cell::default();
-    /// assert_eq!(cell.bg(), Color::Default);
+    /// let mut buf = CellBuffer::new(2, 3, Cell::default());
+    /// assert_eq!(buf.write_vertical(1, 0, "ab", Color::Red, Color::Default, Attr::Default), 2);
+    /// assert_eq!(buf[(1, 0)].ch(), 'a');
+    /// assert_eq!(buf[(1, 1)].ch(), 'b');
+    /// assert_eq!(buf[(1, 1)].fg(), Color::Red);
     ///
-    /// cell.set_bg(Color::Black);
-    /// assert_eq!(cell.bg(), Color::Black);
+    /// // Text running past the bottom edge is clipped.
+    /// let n = buf.write_vertical(0, 1, "xyz", Color::Default, Color::Default, Attr::Default);
+    /// assert_eq!(n, 2);
+    /// assert_eq!(buf[(0, 2)].ch(), 'y');
     /// ```
-    pub fn set_bg(&mut self, newbg: Color) -> &mut Cell {
-        self.bg = newbg;
-        self
+    pub fn write_vertical(&mut self,
+                          x: usize,
+                          y: usize,
+                          s: &str,
+                          fg: Color,
+                          bg: Color,
+                          attrs: Attr)
+                          -> usize {
+        if x >= self.cols {
+            return 0;
+        }
+        let mut written = 0;
+        for (iy, ch) in (y..self.rows).zip(s.chars().filter(|c| !c.is_control())) {
+            self[(x, iy)] = Cell::new(ch, fg, bg, attrs);
+            written += 1;
+        }
+        written
     }
 
-    pub fn attrs(&self) -> Attr {
-        self.attrs
+    /// Returns the smallest rectangle, as `(x, y, w, h)`, enclosing every cell for which `pred`
+    /// returns `true`, or `None` if no cell matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(6, 5, Cell::default());
+    /// buf[(2, 1)].set_ch('a');
+    /// buf[(4, 1)].set_ch('b');
+    /// buf[(3, 3)].set_ch('c');
+    ///
+    /// assert_eq!(buf.bounding_box(|c| c.ch() != ' '), Some((2, 1, 3, 3)));
+    /// assert_eq!(buf.bounding_box(|_| false), None);
+    /// ```
+    pub fn bounding_box<F: Fn(&Cell) -> bool>(&self,
+                                               pred: F)
+                                               -> Option<(usize, usize, usize, usize)> {
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for (i, cell) in self.buf.iter().enumerate() {
+            if !pred(cell) {
+                continue;
+            }
+            let (x, y) = (i % self.cols, i / self.cols);
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+        }
+        bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
     }
 
-    pub fn set_attrs(&mut self, newattrs: Attr) -> &mut Cell {
-        self.attrs = newattrs;
-        self
+    /// Parses a string containing ANSI escape sequences into a `CellBuffer`.
+    ///
+    /// Each line of `s` becomes a row, and the buffer is as wide as the longest line; cells not
+    /// covered by text are left as `Cell::default()`. SGR sequences (`ESC [ ... m`) set the
+    /// colors and attributes of the text that follows, and styles carry over line breaks. Cursor
+    /// position sequences (`ESC [ row ; col H`, 1-based) move to the given cell, so the output of
+    /// `update_bytes` can be read back. Other CSI sequences are skipped, as are control
+    /// characters.
+    ///
+    /// Returns an error if an escape sequence is truncated, is not a CSI sequence, or has a
+    /// non-numeric SGR or cursor position parameter. To keep untrusted input from allocating
+    /// without bound, cursor positions beyond row or column 1024 are rejected as invalid
+    /// parameters, and so is a result of more than 2^22 cells (`AnsiParseError::TooLarge`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr, HasSize, AnsiParseError};
+    ///
+    /// let buf = CellBuffer::from_ansi("ab\n\x1b[1;31mc\x1b[0md").unwrap();
+    /// assert_eq!(buf.size(), (2, 2));
+    /// assert_eq!(buf[(0, 0)], Cell::with_char('a'));
+    /// assert_eq!(buf[(0, 1)], Cell::new('c', Color::Red, Color::Default, Attr::Bold));
+    /// assert_eq!(buf[(1, 1)], Cell::with_char('d'));
+    ///
+    /// // 8-bit and RGB colors are supported as well.
+    /// let buf = CellBuffer::from_ansi("\x1b[38;5;200;48;2;1;2;3mx\x1b[0m\n").unwrap();
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Byte(200));
+    /// assert_eq!(buf[(0, 0)].bg(), Color::Rgb(1, 2, 3));
+    ///
+    /// assert!(CellBuffer::from_ansi("\x1b[31").is_err());
+    /// assert_eq!(CellBuffer::from_ansi("\x1b[1025;1Hx"), Err(AnsiParseError::InvalidParameter));
+    ///
+    /// let wide = format!("{}\n", "x".repeat(5000));
+    /// assert_eq!(CellBuffer::from_ansi(&wide.repeat(1000)), Err(AnsiParseError::TooLarge));
+    /// ```
+    ///
+    /// A buffer survives a round trip through `update_bytes`, as long as every cell is written:
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(4, 3, Cell::default());
+    /// buf[(1, 0)] = Cell::new('a', Color::Red, Color::Default, Attr::Bold);
+    /// buf[(2, 1)] = Cell::new('b', Color::Byte(100), Color::Rgb(1, 2, 3), Attr::Underline);
+    /// buf[(3, 2)].set_ch('c');
+    ///
+    /// let unlike_buf = CellBuffer::new(4, 3, Cell::with_char('?'));
+    /// let ansi = String::from_utf8(buf.update_bytes(&unlike_buf)).unwrap();
+    /// assert_eq!(CellBuffer::from_ansi(&ansi).unwrap(), buf);
+    /// ```
+    pub fn from_ansi(s: &str) -> Result<CellBuffer, AnsiParseError> {
+        let mut lines: Vec<Vec<Cell>> = vec![Vec::new()];
+        let mut style = Cell::default();
+        let (mut x, mut y) = (0, 0);
+        let mut chars = s.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\x1b' => {
+                    match chars.next() {
+                        Some('[') => {}
+                        Some(c) => return Err(AnsiParseError::UnsupportedEscape(c)),
+                        None => return Err(AnsiParseError::Truncated),
+                    }
+                    let mut params = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('m') => {
+                                apply_sgr(&mut style, &params)?;
+                                break;
+                            }
+                            Some('H') | Some('f') => {
+                                let (row, col) = parse_cursor_position(&params)?;
+                                x = col;
+                                y = row;
+                                break;
+                            }
+                            // Any other final byte ends a sequence we don't support.
+                            Some('\x40'..='\x7e') => break,
+                            Some(c) => params.push(c),
+                            None => return Err(AnsiParseError::Truncated),
+                        }
+                    }
+                }
+                '\n' => {
+                    x = 0;
+                    y += 1;
+                    if lines.len() <= y {
+                        lines.resize(y + 1, Vec::new());
+                    }
+                }
+                c if c.is_control() => {}
+                c => {
+                    let mut cell = style;
+                    cell.set_ch(c);
+                    if lines.len() <= y {
+                        lines.resize(y + 1, Vec::new());
+                    }
+                    let line = &mut lines[y];
+                    if line.len() <= x {
+                        line.resize(x + 1, Cell::default());
+                    }
+                    line[x] = cell;
+                    x += 1;
+                }
+            }
+        }
+        if lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+
+        let cols = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+        match cols.checked_mul(lines.len()) {
+            Some(cells) if cells <= ANSI_MAX_CELLS => {}
+            _ => return Err(AnsiParseError::TooLarge),
+        }
+        let mut buf = CellBuffer::new(cols, lines.len(), Cell::default());
+        for (y, line) in lines.iter().enumerate() {
+            buf.buf[y * cols..y * cols + line.len()].copy_from_slice(line);
+        }
+        Ok(buf)
     }
-}
 
-impl Default for Cell {
-    /// Constructs a new `Cell` with a blank `char` and default `Color`s.
+    /// Replaces every foreground and background color in the 16-color range with the
+    /// corresponding entry of `palette`.
+    ///
+    /// The basic colors and `Color::Byte(0x00..0x0f)` are remapped; `Color::Rgb`, higher 8-bit
+    /// colors and `Color::Default` are left untouched.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rustty::{Cell, Color};
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
     ///
-    /// let mut cell = Cell::default();
-    /// assert_eq!(cell.ch(), ' ');
-    /// assert_eq!(cell.fg(), Color::Default);
-    /// assert_eq!(cell.bg(), Color::Default);
+    /// let mut palette = [Color::Black, Color::Red, Color::Green, Color::Yellow, Color::Blue,
+    ///                    Color::Magenta, Color::Cyan, Color::White, Color::Byte(8),
+    ///                    Color::Byte(9), Color::Byte(10), Color::Byte(11), Color::Byte(12),
+    ///                    Color::Byte(13), Color::Byte(14), Color::Byte(15)];
+    /// palette[1] = Color::Rgb(250, 80, 80);
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)] = Cell::with_style(Color::Red, Color::Byte(1), Attr::Default);
+    /// buf[(1, 0)] = Cell::with_style(Color::Rgb(205, 0, 0), Color::Byte(196), Attr::Default);
+    ///
+    /// buf.apply_palette(&palette);
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Rgb(250, 80, 80));
+    /// assert_eq!(buf[(0, 0)].bg(), Color::Rgb(250, 80, 80));
+    /// assert_eq!(buf[(1, 0)].fg(), Color::Rgb(205, 0, 0));
+    /// assert_eq!(buf[(1, 0)].bg(), Color::Byte(196));
+    /// assert_eq!(buf[(2, 0)], Cell::default());
     /// ```
-    fn default() -> Cell {
-        Cell::new(' ', Color::Default, Color::Default, Attr::Default)
+    pub fn apply_palette(&mut self, palette: &[Color; 16]) {
+        let remap = |color: Color| {
+            match color {
+                Color::Rgb(..) | Color::Default => color,
+                c if c.as_byte() < 16 => palette[c.as_byte() as usize],
+                c => c,
+            }
+        };
+        self.touch_all();
+        for cell in self.buf.iter_mut() {
+            let (fg, bg) = (remap(cell.fg()), remap(cell.bg()));
+            cell.set_fg(fg);
+            cell.set_bg(bg);
+        }
     }
-}
+
+    /// Draws a vertical bar chart of `values` into the `w`×`h` region with its top-left corner at
+    /// `(x, y)`.
+    ///
+    /// Each value is scaled relative to the largest value and drawn as a bar of `█` cells rising
+    /// from the bottom of its column, topped with a partial block glyph for the remainder. Values
+    /// beyond the first `w` are ignored, and parts of the region outside the buffer are clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color};
+    ///
+    /// let mut buf = CellBuffer::new(3, 2, Cell::default());
+    /// buf.draw_bar_chart(0, 0, 3, 2, &[0.0, 0.5, 1.0], Color::Green, Color::Default);
+    ///
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "  █ ██");
+    /// assert_eq!(buf[(2, 0)].fg(), Color::Green);
+    ///
+    /// buf.draw_bar_chart(0, 0, 3, 2, &[1.0, 0.25], Color::Green, Color::Default);
+    /// assert_eq!(buf[(1, 1)].ch(), '▄');
+    ///
+    /// // Only the visible top rows of a very tall region are drawn.
+    /// buf.draw_bar_chart(0, 0, 3, usize::MAX, &[1.0, 0.0], Color::Green, Color::Default);
+    /// assert_eq!(buf[(0, 1)].ch(), '█');
+    /// assert_eq!(buf[(1, 1)].ch(), ' ');
+    /// buf.draw_bar_chart(0, usize::MAX, 3, usize::MAX, &[1.0], Color::Red, Color::Default);
+    /// assert_eq!(buf[(0, 1)].fg(), Color::Green);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_bar_chart(&mut self,
+                          x: usize,
+                          y: usize,
+                          w: usize,
+                          h: usize,
+                          values: &[f32],
+                          fg: Color,
+                          bg: Color) {
+        let max = values.iter().take(w).cloned().fold(0.0, f32::max);
+        // Bars are scaled to the full height of the region, even where it is clipped.
+        let (_, _, cw, ch) = self.clamp_rect(x, y, w, h);
+        for (i, &value) in values.iter().take(cw).enumerate() {
+            let fraction = if max > 0.0 { value.max(0.0) / max } else { 0.0 };
+            // Measured from the top, so the visible rows of a very tall region stay precise.
+            let empty = ((1.0 - fraction) * h as f32 * 8.0).round() as usize;
+            for iy in y..y + ch {
+                let filled = ((iy - y + 1) * 8).saturating_sub(empty).min(8);
+                self[(x + i, iy)] = Cell::new(BLOCK_GLYPHS[filled], fg, bg, Attr::Default);
+            }
+        }
+    }
+
+    /// Sets the colors and attributes of every cell in the `w`×`h` region with its top-left corner
+    /// at `(x, y)`, leaving the characters intact.
+    ///
+    /// Parts of the region outside the buffer are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(4, 2, Cell::default());
+    /// buf[(1, 0)].set_ch('h');
+    /// buf[(2, 0)].set_ch('i');
+    ///
+    /// buf.set_style_region(1, 0, 10, 1, Color::Yellow, Color::Blue, Attr::Bold);
+    /// assert_eq!(buf[(1, 0)], Cell::new('h', Color::Yellow, Color::Blue, Attr::Bold));
+    /// assert_eq!(buf[(2, 0)], Cell::new('i', Color::Yellow, Color::Blue, Attr::Bold));
+    /// assert_eq!(buf[(0, 0)], Cell::default());
+    /// assert_eq!(buf[(1, 1)], Cell::default());
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_style_region(&mut self,
+                            x: usize,
+                            y: usize,
+                            w: usize,
+                            h: usize,
+                            fg: Color,
+                            bg: Color,
+                            attrs: Attr) {
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        for iy in y..y + h {
+            for ix in x..x + w {
+                let cell = &mut self[(ix, iy)];
+                cell.set_fg(fg);
+                cell.set_bg(bg);
+                cell.set_attrs(attrs);
+            }
+        }
+    }
+
+    /// Scrolls the contents of the `w`×`h` region with its top-left corner at `(x, y)` up by `n`
+    /// rows, filling the vacated rows at the bottom of the region with `blank`.
+    ///
+    /// Cells outside the region, including any part of it lying outside the buffer, are
+    /// untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(3, 4, Cell::default());
+    /// for y in 0..4 {
+    ///     for x in 0..3 {
+    ///         buf[(x, y)].set_ch((b'0' + y as u8) as char);
+    ///     }
+    /// }
+    ///
+    /// buf.scroll_region_up(1, 1, 1, 2, 1, Cell::with_char('.'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "0001212.2333");
+    ///
+    /// // Scrolling by more than the region's height blanks all of it.
+    /// buf.scroll_region_up(0, 2, 2, 1, usize::MAX, Cell::with_char('#'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "000121##2333");
+    ///
+    /// // A region reaching past the buffer is clipped to it.
+    /// buf.scroll_region_up(1, 1, usize::MAX, usize::MAX, 1, Cell::with_char('.'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "0001#2#333..");
+    /// ```
+    pub fn scroll_region_up(&mut self,
+                            x: usize,
+                            y: usize,
+                            w: usize,
+                            h: usize,
+                            n: usize,
+                            blank: Cell) {
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        let n = n.min(h);
+        for row in 0..h {
+            for ix in x..x + w {
+                self[(ix, y + row)] = if row + n < h {
+                    self[(ix, y + row + n)]
+                } else {
+                    blank
+                };
+            }
+        }
+    }
+
+    /// Scrolls the contents of the `w`×`h` region with its top-left corner at `(x, y)` down by `n`
+    /// rows, filling the vacated rows at the top of the region with `blank`.
+    ///
+    /// Cells outside the region, including any part of it lying outside the buffer, are
+    /// untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(3, 4, Cell::default());
+    /// for y in 0..4 {
+    ///     for x in 0..3 {
+    ///         buf[(x, y)].set_ch((b'0' + y as u8) as char);
+    ///     }
+    /// }
+    ///
+    /// buf.scroll_region_down(1, 1, 1, 2, 1, Cell::with_char('.'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "0001.1212333");
+    ///
+    /// // Scrolling by more than the region's height blanks all of it.
+    /// buf.scroll_region_down(0, 1, 3, 2, usize::MAX, Cell::with_char('#'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "000######333");
+    /// ```
+    pub fn scroll_region_down(&mut self,
+                              x: usize,
+                              y: usize,
+                              w: usize,
+                              h: usize,
+                              n: usize,
+                              blank: Cell) {
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        let n = n.min(h);
+        for row in (0..h).rev() {
+            for ix in x..x + w {
+                self[(ix, y + row)] = if row >= n {
+                    self[(ix, y + row - n)]
+                } else {
+                    blank
+                };
+            }
+        }
+    }
+
+    /// Returns the bytes that update a terminal displaying `prev` to display `self`.
+    ///
+    /// This collects the output of `write_update` into a `Vec`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are of different sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let prev = CellBuffer::new(5, 5, Cell::default());
+    /// let mut next = prev.clone();
+    /// next[(2, 3)] = Cell::new('x', Color::Red, Color::Default, Attr::Default);
+    ///
+    /// assert_eq!(next.update_bytes(&prev), b"\x1b[4;3H\x1b[0;31mx\x1b[0m".to_vec());
+    /// assert!(next.update_bytes(&next).is_empty());
+    /// ```
+    pub fn update_bytes(&self, prev: &CellBuffer) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_update(prev, &mut out).expect("writing to a Vec can't fail");
+        out
+    }
+
+    /// Writes the bytes that update a terminal displaying `prev` to display `self` to `out`.
+    ///
+    /// Each run of changed cells in a row is preceded by a cursor movement (`ESC [ row ; col H`,
+    /// 1-based), and an SGR sequence is emitted whenever the style changes between cells. The
+    /// terminal is assumed to start with the default style, and is reset to it at the end if
+    /// necessary. Any error returned by `out` is passed on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are of different sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let prev = CellBuffer::new(5, 5, Cell::default());
+    /// let mut next = prev.clone();
+    /// next[(0, 1)].set_ch('x');
+    ///
+    /// let mut out = Vec::new();
+    /// next.write_update(&prev, &mut out).unwrap();
+    /// assert_eq!(out, b"\x1b[2;1Hx");
+    /// ```
+    pub fn write_update<W: Write>(&self, prev: &CellBuffer, out: &mut W) -> io::Result<()> {
+        assert_eq!(self.size(), prev.size(), "buffers must be the same size");
+        let mut style = Cell::default();
+        for y in 0..self.rows {
+            let mut in_run = false;
+            for x in 0..self.cols {
+                let cell = self[(x, y)];
+                if cell == prev[(x, y)] {
+                    in_run = false;
+                    continue;
+                }
+                if !in_run {
+                    write!(out, "\x1b[{};{}H", y + 1, x + 1)?;
+                    in_run = true;
+                }
+                if !cell.same_style(&style) {
+                    out.write_all(sgr_sequence(&cell).as_bytes())?;
+                    style = cell;
+                }
+                let mut utf8 = [0; 4];
+                out.write_all(cell.ch().encode_utf8(&mut utf8).as_bytes())?;
+            }
+        }
+        if !style.same_style(&Cell::default()) {
+            out.write_all(b"\x1b[0m")?;
+        }
+        Ok(())
+    }
+
+    /// Splits row `y` into maximal runs of cells sharing the same style, returning the starting
+    /// column and the cells of each run. Returns an empty `Vec` if `y` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(5, 2, Cell::default());
+    /// for x in 2..5 {
+    ///     buf[(x, 0)] = Cell::new('r', Color::Red, Color::Default, Attr::Default);
+    /// }
+    ///
+    /// let runs = buf.row_runs(0);
+    /// assert_eq!(runs.len(), 2);
+    /// assert_eq!((runs[0].0, runs[0].1.len()), (0, 2));
+    /// assert_eq!((runs[1].0, runs[1].1.len()), (2, 3));
+    /// assert!(buf.row_runs(2).is_empty());
+    /// ```
+    pub fn row_runs(&self, y: usize) -> Vec<(usize, &[Cell])> {
+        let mut runs = Vec::new();
+        if y >= self.rows {
+            return runs;
+        }
+        let row = &self.buf[y * self.cols..(y + 1) * self.cols];
+        let mut start = 0;
+        for x in 1..=row.len() {
+            if x == row.len() || !row[x].same_style(&row[start]) {
+                runs.push((start, &row[start..x]));
+                start = x;
+            }
+        }
+        runs
+    }
+
+    /// Returns the total number of cells in the buffer, `cols * rows`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let buf = CellBuffer::new(4, 3, Cell::default());
+    /// assert_eq!(buf.area(), 12);
+    /// ```
+    pub fn area(&self) -> usize {
+        self.cols * self.rows
+    }
+
+    /// Returns `true` if the buffer has zero columns or zero rows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// assert!(CellBuffer::new(0, 5, Cell::default()).is_degenerate());
+    /// assert!(!CellBuffer::new(4, 3, Cell::default()).is_degenerate());
+    /// ```
+    pub fn is_degenerate(&self) -> bool {
+        self.cols == 0 || self.rows == 0
+    }
+
+    /// Blends `top` over the buffer with its top-left corner at `(x, y)`.
+    ///
+    /// The colors of each overlapping cell are blended towards those of `top` by `alpha` (see
+    /// `Color::blend`). Where `top` holds a non-blank character, that character and its
+    /// attributes replace those underneath. Parts of `top` outside the buffer are clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let base = Cell::new('a', Color::Default, Color::Rgb(0, 0, 0), Attr::Default);
+    /// let tint = Cell::with_style(Color::Default, Color::Rgb(200, 100, 50), Attr::Default);
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, base);
+    /// let mut top = CellBuffer::new(2, 1, tint);
+    /// top[(1, 0)].set_ch('b');
+    ///
+    /// buf.blend_over(&top, 1, 0, 0.5);
+    /// assert_eq!(buf[(0, 0)].bg(), Color::Rgb(0, 0, 0));
+    /// assert_eq!(buf[(1, 0)].bg(), Color::Rgb(100, 50, 25));
+    /// assert_eq!(buf[(1, 0)].ch(), 'a');
+    /// assert_eq!(buf[(2, 0)].ch(), 'b');
+    ///
+    /// buf.blend_over(&top, usize::MAX, 0, 0.5);
+    /// assert_eq!(buf[(2, 0)].ch(), 'b');
+    /// ```
+    pub fn blend_over(&mut self, top: &CellBuffer, x: usize, y: usize, alpha: f32) {
+        let (_, _, w, h) = self.clamp_rect(x, y, top.cols, top.rows);
+        for ty in 0..h {
+            for tx in 0..w {
+                let src = top[(tx, ty)];
+                let cell = &mut self[(x + tx, y + ty)];
+                let fg = cell.fg().blend(src.fg(), alpha);
+                let bg = cell.bg().blend(src.bg(), alpha);
+                cell.set_fg(fg);
+                cell.set_bg(bg);
+                if src.ch() != ' ' {
+                    cell.set_ch(src.ch());
+                    cell.set_attrs(src.attrs());
+                }
+            }
+        }
+    }
+
+    /// Returns the number of rows for which `pred` returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(3, 4, Cell::default());
+    /// buf[(0, 0)].set_ch('x');
+    /// buf[(2, 2)].set_ch('x');
+    /// buf[(1, 2)].set_ch('y');
+    ///
+    /// assert_eq!(buf.count_rows_matching(|row| row.iter().all(|c| c.ch() == ' ')), 2);
+    /// assert_eq!(buf.count_rows_matching(|row| row.iter().any(|c| c.ch() == 'x')), 2);
+    /// ```
+    pub fn count_rows_matching<F: Fn(&[Cell]) -> bool>(&self, pred: F) -> usize {
+        if self.cols == 0 {
+            return if pred(&[]) { self.rows } else { 0 };
+        }
+        self.buf.chunks(self.cols).filter(|row| pred(row)).count()
+    }
+
+    /// Returns a copy of the buffer with every cell replaced by its `Cell::ascii_fallback`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    /// use rustty::ui::Painter;
+    ///
+    /// let mut buf = CellBuffer::new(3, 3, Cell::default());
+    /// buf.draw_box();
+    ///
+    /// let text: String = buf.to_ascii().iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "+-+| |+-+");
+    /// ```
+    pub fn to_ascii(&self) -> CellBuffer {
+        CellBuffer::from_vec(self.cols,
+                             self.rows,
+                             self.buf.iter().map(|c| c.ascii_fallback()).collect())
+    }
+
+    /// Writes each `(text, fg, bg, attrs)` segment along row `y`, starting at column `x` and
+    /// continuing where the previous segment ended.
+    ///
+    /// Writing stops at the right edge of the buffer. Returns the total number of columns written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(10, 1, Cell::default());
+    /// let n = buf.write_segments(1, 0, &[("12:00", Color::Blue, Color::Default, Attr::Default),
+    ///                                    (" ", Color::Default, Color::Default, Attr::Default),
+    ///                                    ("WARN", Color::Yellow, Color::Default, Attr::Bold)]);
+    /// assert_eq!(n, 9);
+    /// assert_eq!(buf[(1, 0)].fg(), Color::Blue);
+    /// assert_eq!(buf[(5, 0)].ch(), '0');
+    /// assert_eq!(buf[(7, 0)], Cell::new('W', Color::Yellow, Color::Default, Attr::Bold));
+    /// assert_eq!(buf[(9, 0)].ch(), 'R');
+    /// ```
+    pub fn write_segments(&mut self,
+                          x: usize,
+                          y: usize,
+                          segments: &[(&str, Color, Color, Attr)])
+                          -> usize {
+        if y >= self.rows {
+            return 0;
+        }
+        let mut ix = x;
+        for &(text, fg, bg, attrs) in segments {
+            for ch in text.chars() {
+                if ix >= self.cols {
+                    return ix.saturating_sub(x);
+                }
+                self[(ix, y)] = Cell::new(ch, fg, bg, attrs);
+                ix += 1;
+            }
+        }
+        ix - x
+    }
+
+    /// Multiplies the RGB channels of every cell's foreground and background colors by `factor`.
+    ///
+    /// Colors are stored back as `Color::Rgb`; `Color::Default` is left untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let cell = Cell::new('x', Color::Rgb(200, 100, 50), Color::White, Attr::Default);
+    /// let mut buf = CellBuffer::new(2, 2, cell);
+    ///
+    /// buf.dim(0.5);
+    /// assert!(buf.iter().all(|c| c.ch() == 'x'));
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Rgb(100, 50, 25));
+    /// assert_eq!(buf[(1, 1)].bg(), Color::Rgb(115, 115, 115));
+    /// ```
+    pub fn dim(&mut self, factor: f32) {
+        self.touch_all();
+        for cell in self.buf.iter_mut() {
+            let (fg, bg) = (cell.fg().scale(factor), cell.bg().scale(factor));
+            cell.set_fg(fg);
+            cell.set_bg(bg);
+        }
+    }
+
+    /// Returns an iterator over the `(x, y, ch)` of every cell whose character isn't a space, in
+    /// row-major order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(4, 3, Cell::default());
+    /// buf[(3, 0)].set_ch('h');
+    /// buf[(1, 2)].set_ch('i');
+    ///
+    /// let chars: Vec<_> = buf.chars_iter().collect();
+    /// assert_eq!(chars, vec![(3, 0, 'h'), (1, 2, 'i')]);
+    /// ```
+    pub fn chars_iter(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+        let cols = self.cols;
+        self.buf
+            .iter()
+            .enumerate()
+            .filter(|&(_, c)| c.ch() != ' ')
+            .map(move |(i, c)| (i % cols, i / cols, c.ch()))
+    }
+
+    /// Grows the buffer, if necessary, so that `(x, y)` is in bounds, using the given `Cell` as a
+    /// blank for new cells.
+    ///
+    /// Existing content keeps its position and the buffer never shrinks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, HasSize, CellAccessor};
+    ///
+    /// let mut buf = CellBuffer::new(2, 2, Cell::with_char('x'));
+    /// buf.grow_to_fit(4, 1, Cell::default());
+    /// assert_eq!(buf.size(), (5, 2));
+    /// assert_eq!(buf[(1, 1)].ch(), 'x');
+    /// assert_eq!(buf[(4, 1)], Cell::default());
+    ///
+    /// buf[(4, 1)].set_ch('y');
+    /// buf.grow_to_fit(0, 0, Cell::default());
+    /// assert_eq!(buf.size(), (5, 2));
+    /// assert_eq!(buf.get(4, 1).map(|c| c.ch()), Some('y'));
+    /// ```
+    pub fn grow_to_fit(&mut self, x: usize, y: usize, blank: Cell) {
+        let newcols = self.cols.max(x + 1);
+        let newrows = self.rows.max(y + 1);
+        if (newcols, newrows) != (self.cols, self.rows) {
+            self.resize(newcols, newrows, blank);
+        }
+    }
+
+    /// Returns the number of rows `text` occupies when word-wrapped to `width` columns.
+    ///
+    /// Lines are broken at whitespace where possible, and words longer than `width` are broken
+    /// across rows. Each `\n` forces a line break, so blank lines occupy a row of their own. A
+    /// `width` of zero can't hold any text, so the result is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::CellBuffer;
+    ///
+    /// assert_eq!(CellBuffer::measure_wrapped("the quick brown fox", 10), 2);
+    /// assert_eq!(CellBuffer::measure_wrapped("abcdefghij", 4), 3);
+    /// assert_eq!(CellBuffer::measure_wrapped("a\n\nb", 5), 3);
+    /// assert_eq!(CellBuffer::measure_wrapped("", 5), 0);
+    /// assert_eq!(CellBuffer::measure_wrapped("text", 0), 0);
+    /// ```
+    pub fn measure_wrapped(text: &str, width: usize) -> usize {
+        wrap_text(text, width).len()
+    }
+
+    /// Copies `src` into the buffer with its top-left corner at `(x, y)`.
+    ///
+    /// Cells of `src` holding the `transparent` character are skipped, letting the cells beneath
+    /// show through. If `tint` is `Some`, the foreground of every copied cell is set to it. Parts
+    /// of `src` outside the buffer are clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color};
+    ///
+    /// let mut sprite = CellBuffer::new(3, 1, Cell::with_char('#'));
+    /// sprite[(1, 0)].set_ch('.');
+    ///
+    /// let mut buf = CellBuffer::new(4, 1, Cell::with_char('-'));
+    /// buf.stamp(&sprite, 1, 0, '.', Some(Color::Red));
+    ///
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "-#-#");
+    /// assert_eq!(buf[(1, 0)].fg(), Color::Red);
+    /// assert_eq!(buf[(2, 0)].fg(), Color::Default);
+    ///
+    /// buf.stamp(&sprite, usize::MAX, 0, '.', None);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "-#-#");
+    /// ```
+    pub fn stamp(&mut self,
+                 src: &CellBuffer,
+                 x: usize,
+                 y: usize,
+                 transparent: char,
+                 tint: Option<Color>) {
+        let (_, _, w, h) = self.clamp_rect(x, y, src.cols, src.rows);
+        for sy in 0..h {
+            for sx in 0..w {
+                let mut cell = src[(sx, sy)];
+                if cell.ch() == transparent {
+                    continue;
+                }
+                if let Some(fg) = tint {
+                    cell.set_fg(fg);
+                }
+                self[(x + sx, y + sy)] = cell;
+            }
+        }
+    }
+
+    /// Returns the current version of the buffer.
+    ///
+    /// The version starts at 0 and grows with every write. With `set_version_tracking` enabled,
+    /// each cell is also stamped with the version of the last write to it: writes through
+    /// `IndexMut`, `get_mut` and the region methods stamp just the cells they touch, while mutable
+    /// access to all the cells at once (through `DerefMut` or `cellvec_mut`), resizing and
+    /// whole-buffer operations stamp every cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(3, 3, Cell::default());
+    /// let v = buf.version();
+    /// assert_eq!(v, 0);
+    /// assert_eq!(buf[(1, 1)].ch(), ' ');
+    /// assert_eq!(buf.version(), v);
+    ///
+    /// buf[(1, 1)].set_ch('x');
+    /// assert!(buf.version() > v);
+    /// ```
+    pub fn version(&self) -> u64 {
+        self.versions.current
+    }
+
+    /// Returns an iterator over the `(x, y, cell)` of every cell written after version `v`, in
+    /// row-major order.
+    ///
+    /// Cells are reported when written, even if their value didn't change. Unless tracking is
+    /// enabled with `set_version_tracking`, every cell is reported if anything was written after
+    /// version `v`, and for versions from before tracking was enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(4, 4, Cell::default());
+    /// buf.set_version_tracking(true);
+    /// let v = buf.version();
+    ///
+    /// buf[(1, 2)].set_ch('a');
+    /// buf[(3, 0)].set_ch('b');
+    /// let changed: Vec<_> = buf.changed_since(v).map(|(x, y, c)| (x, y, c.ch())).collect();
+    /// assert_eq!(changed, vec![(3, 0, 'b'), (1, 2, 'a')]);
+    ///
+    /// let v2 = buf.version();
+    /// assert_eq!(buf.changed_since(v2).count(), 0);
+    /// assert_eq!(buf.changed_since(v).count(), 2);
+    ///
+    /// buf.set_style_region(0, 3, 2, 1, Color::Red, Color::Default, Attr::Default);
+    /// let changed: Vec<_> = buf.changed_since(v2).map(|(x, y, _)| (x, y)).collect();
+    /// assert_eq!(changed, vec![(0, 3), (1, 3)]);
+    /// ```
+    pub fn changed_since(&self, v: u64) -> impl Iterator<Item = (usize, usize, &Cell)> {
+        let cols = self.cols;
+        self.buf
+            .iter()
+            .enumerate()
+            .filter(move |&(i, _)| self.versions.stamp(i) > v)
+            .map(move |(i, cell)| (i % cols, i / cols, cell))
+    }
+
+    /// Enables or disables stamping each cell with the version of the last write to it, for
+    /// `changed_since`.
+    ///
+    /// Tracking is disabled by default, as it takes an extra 8 bytes per cell. Enabling it
+    /// counts every cell as written at the current version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// let v = buf.version();
+    /// buf[(0, 0)].set_ch('a');
+    /// assert_eq!(buf.changed_since(v).count(), 3);
+    ///
+    /// buf.set_version_tracking(true);
+    /// let v = buf.version();
+    /// buf[(0, 0)].set_ch('b');
+    /// assert_eq!(buf.changed_since(v).count(), 1);
+    /// ```
+    pub fn set_version_tracking(&mut self, enabled: bool) {
+        let versions = &mut self.versions;
+        if !enabled {
+            versions.stamps = None;
+        } else if versions.stamps.is_none() {
+            versions.all = versions.current;
+            versions.stamps = Some(vec![0; self.buf.len()]);
+        }
+    }
+
+    /// Writes `s` along row `y` starting at column `x`, with foreground colors running from
+    /// `start` at the first character to `end` at the last (see `Color::blend`).
+    ///
+    /// Writing stops at the right edge of the buffer. Returns the number of columns written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(5, 1, Cell::default());
+    /// let (start, end) = (Color::Rgb(0, 0, 0), Color::Rgb(200, 100, 0));
+    /// let n = buf.write_gradient_str(0, 0, "hello", start, end, Color::Default, Attr::Default);
+    /// assert_eq!(n, 5);
+    /// assert_eq!(buf[(0, 0)].fg(), start);
+    /// assert_eq!(buf[(2, 0)].fg(), Color::Rgb(100, 50, 0));
+    /// assert_eq!(buf[(4, 0)].fg(), end);
+    /// assert_eq!(buf[(4, 0)].ch(), 'o');
+    ///
+    /// let far = usize::MAX;
+    /// let n = buf.write_gradient_str(far, 0, "hi", start, end, Color::Default, Attr::Default);
+    /// assert_eq!(n, 0);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_gradient_str(&mut self,
+                              x: usize,
+                              y: usize,
+                              s: &str,
+                              start: Color,
+                              end: Color,
+                              bg: Color,
+                              attrs: Attr)
+                              -> usize {
+        let steps = s.chars().count().saturating_sub(1).max(1) as f32;
+        let mut written = 0;
+        for (i, ch) in s.chars().enumerate() {
+            match self.get_mut(x.saturating_add(i), y) {
+                Some(cell) => *cell = Cell::new(ch, start.blend(end, i as f32 / steps), bg, attrs),
+                None => break,
+            }
+            written += 1;
+        }
+        written
+    }
+
+    /// Replaces every non-blank character in the `w`×`h` region with its top-left corner at
+    /// `(x, y)` with `mask`, leaving blank cells and styles untouched.
+    ///
+    /// Parts of the region outside the buffer are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(6, 1, Cell::default());
+    /// for (x, ch) in "pw: hi".chars().enumerate() {
+    ///     buf[(x, 0)] = Cell::new(ch, Color::Green, Color::Default, Attr::Default);
+    /// }
+    ///
+    /// buf.redact_region(3, 0, 10, 1, '*');
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "pw: **");
+    /// assert!(buf.iter().all(|c| c.fg() == Color::Green));
+    /// ```
+    pub fn redact_region(&mut self, x: usize, y: usize, w: usize, h: usize, mask: char) {
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        for iy in y..y + h {
+            for ix in x..x + w {
+                let cell = &mut self[(ix, iy)];
+                if cell.ch() != ' ' {
+                    cell.set_ch(mask);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of distinct `(fg, bg, attrs)` styles used by the cells of the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(4, 1, Cell::default());
+    /// buf[(0, 0)] = Cell::new('a', Color::Red, Color::Default, Attr::Default);
+    /// buf[(1, 0)] = Cell::new('b', Color::Red, Color::Default, Attr::Default);
+    /// buf[(2, 0)] = Cell::new('a', Color::Red, Color::Default, Attr::Bold);
+    ///
+    /// assert_eq!(buf.distinct_styles(), 3);
+    /// ```
+    pub fn distinct_styles(&self) -> usize {
+        self.buf
+            .iter()
+            .map(|c| (c.fg(), c.bg(), c.attrs()))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Renders the buffer as an HTML `<pre>` block.
+    ///
+    /// Each run of same-styled cells in a row becomes a `<span>` with an inline `style`; runs in
+    /// the default style are written as plain text. `Color::Default` sets no color, so it
+    /// inherits from the surrounding page. Characters special to HTML are escaped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(3, 2, Cell::default());
+    /// buf[(0, 0)] = Cell::new('x', Color::Red, Color::Default, Attr::Default);
+    /// buf[(1, 0)] = Cell::new('y', Color::Red, Color::Default, Attr::Default);
+    /// buf[(0, 1)].set_ch('<');
+    ///
+    /// assert_eq!(buf.to_html(),
+    ///            "<pre><span style=\"color:#cd0000\">xy</span> \n&lt;  </pre>");
+    /// ```
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<pre>");
+        for y in 0..self.rows {
+            if y > 0 {
+                html.push('\n');
+            }
+            for (_, run) in self.row_runs(y) {
+                let style = html_style(&run[0]);
+                if !style.is_empty() {
+                    html.push_str(&format!("<span style=\"{}\">", style));
+                }
+                for cell in run {
+                    match cell.ch() {
+                        '<' => html.push_str("&lt;"),
+                        '>' => html.push_str("&gt;"),
+                        '&' => html.push_str("&amp;"),
+                        '"' => html.push_str("&quot;"),
+                        ch => html.push(ch),
+                    }
+                }
+                if !style.is_empty() {
+                    html.push_str("</span>");
+                }
+            }
+        }
+        html.push_str("</pre>");
+        html
+    }
+
+    /// Returns a `target_cols`×`target_rows` buffer holding a copy of `self` scaled down to fit,
+    /// preserving its aspect ratio, and centered with the margins filled with `blank`.
+    ///
+    /// Scaling samples the nearest source cell. Content that already fits is not enlarged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, HasSize};
+    ///
+    /// let mut buf = CellBuffer::new(8, 2, Cell::default());
+    /// for (x, ch) in "abcdefgh".chars().enumerate() {
+    ///     buf[(x, 0)].set_ch(ch);
+    ///     buf[(x, 1)].set_ch(ch);
+    /// }
+    ///
+    /// let fitted = buf.fit_into(4, 4, Cell::with_char('.'));
+    /// assert_eq!(fitted.size(), (4, 4));
+    /// let text: String = fitted.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "....aceg........");
+    ///
+    /// let line = CellBuffer::new(100, 1, Cell::with_char('x'));
+    /// let fitted = line.fit_into(10, 10, Cell::with_char('.'));
+    /// let text: String = fitted.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(&text[30..50], "..........xxxxxxxxxx");
+    /// ```
+    pub fn fit_into(&self, target_cols: usize, target_rows: usize, blank: Cell) -> CellBuffer {
+        let mut fitted = CellBuffer::new(target_cols, target_rows, blank);
+        if self.is_degenerate() {
+            return fitted;
+        }
+        let scale = (target_cols as f32 / self.cols as f32)
+            .min(target_rows as f32 / self.rows as f32)
+            .min(1.0);
+        // Thin content keeps at least one row or column rather than vanishing.
+        let cols = ((self.cols as f32 * scale).round() as usize).max(1).min(target_cols);
+        let rows = ((self.rows as f32 * scale).round() as usize).max(1).min(target_rows);
+        let (left, top) = ((target_cols - cols) / 2, (target_rows - rows) / 2);
+        for y in 0..rows {
+            for x in 0..cols {
+                fitted[(left + x, top + y)] = self[(x * self.cols / cols, y * self.rows / rows)];
+            }
+        }
+        fitted
+    }
+
+    /// Returns an iterator over the `(x, y, cell)` of every cell of `self` that differs from the
+    /// corresponding cell of `other`, in row-major order.
+    ///
+    /// Cells are compared lazily as the iterator is advanced, so nothing is allocated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are of different sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let old = CellBuffer::new(3, 2, Cell::default());
+    /// let mut new = old.clone();
+    /// new[(2, 0)].set_ch('a');
+    /// new[(0, 1)].set_ch('b');
+    ///
+    /// let changes: Vec<_> = new.diff_iter(&old).map(|(x, y, c)| (x, y, c.ch())).collect();
+    /// assert_eq!(changes, vec![(2, 0, 'a'), (0, 1, 'b')]);
+    /// assert_eq!(old.diff_iter(&old).next(), None);
+    /// ```
+    pub fn diff_iter<'a>(&'a self,
+                         other: &'a CellBuffer)
+                         -> impl Iterator<Item = (usize, usize, &'a Cell)> {
+        assert_eq!(self.size(), other.size(), "buffers must be the same size");
+        let cols = self.cols;
+        self.buf
+            .iter()
+            .zip(other.buf.iter())
+            .enumerate()
+            .filter(|&(_, (a, b))| a != b)
+            .map(move |(i, (a, _))| (i % cols, i / cols, a))
+    }
+
+    /// Returns the coordinates of every cell for which `pred` returns `true`, in row-major order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(4, 3, Cell::default());
+    /// buf[(3, 2)].set_ch('@');
+    /// buf[(1, 0)].set_ch('@');
+    /// buf[(0, 2)].set_ch('@');
+    ///
+    /// assert_eq!(buf.find_all(|c| c.ch() == '@'), vec![(1, 0), (0, 2), (3, 2)]);
+    /// assert!(buf.find_all(|c| c.ch() == '#').is_empty());
+    /// ```
+    pub fn find_all<F: Fn(&Cell) -> bool>(&self, pred: F) -> Vec<(usize, usize)> {
+        self.buf
+            .iter()
+            .enumerate()
+            .filter(|&(_, cell)| pred(cell))
+            .map(|(i, _)| (i % self.cols, i / self.cols))
+            .collect()
+    }
+
+    /// Resizes the buffer to the given number of columns and rows, keeping the existing content
+    /// centered and using the given `Cell` as a blank.
+    ///
+    /// Growing adds equal margins on each side, and shrinking crops equally from each side; when
+    /// the change is odd, the extra column or row is added or removed on the right or bottom. Like
+    /// `resize`, the cells are rearranged in place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, CellAccessor, HasSize};
+    ///
+    /// let mut buf = CellBuffer::with_reserved(2, 2, 4, 4);
+    /// let capacity = buf.capacity();
+    /// buf.clear(Cell::with_char('x'));
+    /// buf.resize_centered(4, 4, Cell::with_char('.'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, ".....xx..xx.....");
+    ///
+    /// buf[(0, 1)].set_ch('a');
+    /// buf.resize_centered(2, 2, Cell::default());
+    /// assert_eq!(buf.size(), (2, 2));
+    /// assert!(buf.iter().all(|c| c.ch() == 'x'));
+    /// assert_eq!(buf.capacity(), capacity);
+    ///
+    /// let mut buf = CellBuffer::new(4, 2, Cell::default());
+    /// for (i, ch) in "abcdefgh".chars().enumerate() {
+    ///     buf[(i % 4, i / 4)].set_ch(ch);
+    /// }
+    /// buf.resize_centered(2, 4, Cell::with_char('.'));
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "..bcfg..");
+    /// ```
+    pub fn resize_centered(&mut self, newcols: usize, newrows: usize, blank: Cell) {
+        let (cols, rows) = (self.cols, self.rows);
+        // Cropping packs the kept cells at the front and growing spreads them out from the back,
+        // so in each pass a cell only moves in the direction it is walked and is never
+        // overwritten before it has been read.
+        let (keptcols, keptrows) = (cmp::min(cols, newcols), cmp::min(rows, newrows));
+        let (left, top) = ((cols - keptcols) / 2, (rows - keptrows) / 2);
+        for y in 0..keptrows {
+            for x in 0..keptcols {
+                self.buf[y * keptcols + x] = self.buf[(y + top) * cols + x + left];
+            }
+        }
+
+        let newlen = newcols * newrows;
+        if newlen > self.buf.len() {
+            self.buf.resize(newlen, blank);
+        }
+        let (left, top) = ((newcols - keptcols) / 2, (newrows - keptrows) / 2);
+        for y in (0..newrows).rev() {
+            for x in (0..newcols).rev() {
+                let kept = x >= left && x < left + keptcols && y >= top && y < top + keptrows;
+                self.buf[y * newcols + x] = if kept {
+                    self.buf[(y - top) * keptcols + x - left]
+                } else {
+                    blank
+                };
+            }
+        }
+        self.buf.truncate(newlen);
+        self.touch_all();
+        self.cols = newcols;
+        self.rows = newrows;
+    }
+
+    /// Adds `attr` to the attributes of every cell whose entry in `mask` is `true`.
+    ///
+    /// `mask` holds one entry per cell, in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `mask` differs from the number of cells in the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let bold = Cell::with_style(Color::Default, Color::Default, Attr::Bold);
+    /// let mut buf = CellBuffer::new(2, 2, bold);
+    /// buf.apply_attr_masked(&[false, true, false, false], Attr::Reverse);
+    ///
+    /// assert_eq!(buf[(1, 0)].attrs(), Attr::BoldReverse);
+    /// assert_eq!(buf[(0, 0)].attrs(), Attr::Bold);
+    /// assert_eq!(buf[(1, 1)].attrs(), Attr::Bold);
+    /// ```
+    pub fn apply_attr_masked(&mut self, mask: &[bool], attr: Attr) {
+        assert_eq!(mask.len(),
+                   self.buf.len(),
+                   "mask length must equal the number of cells in the buffer");
+        self.touch_all();
+        for (cell, &masked) in self.buf.iter_mut().zip(mask) {
+            if masked {
+                let attrs = cell.attrs() | attr;
+                cell.set_attrs(attrs);
+            }
+        }
+    }
+
+    /// Overwrites the rows of the buffer starting at row `start_y` with the rows of `src`.
+    ///
+    /// Rows of `src` that would fall below the bottom of the buffer are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` has a different number of columns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(2, 5, Cell::with_char('.'));
+    /// let src = CellBuffer::new(2, 3, Cell::with_char('x'));
+    ///
+    /// buf.splice_rows(1, &src);
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "..xxxxxx..");
+    ///
+    /// buf.splice_rows(4, &src);
+    /// assert_eq!(buf[(0, 4)].ch(), 'x');
+    /// buf.splice_rows(9, &src);
+    /// ```
+    pub fn splice_rows(&mut self, start_y: usize, src: &CellBuffer) {
+        assert_eq!(self.cols, src.cols, "buffers must have the same number of columns");
+        let rows = src.rows.min(self.rows.saturating_sub(start_y));
+        if rows == 0 {
+            return;
+        }
+        let (start, len) = (start_y * self.cols, rows * self.cols);
+        self.buf[start..start + len].copy_from_slice(&src.buf[..len]);
+        self.versions.touch_range(start..start + len);
+    }
+
+    /// Draws the outline of an ellipse centered at `(cx, cy)` with horizontal radius `rx` and
+    /// vertical radius `ry`, setting each point on it to `cell`.
+    ///
+    /// The outline is rasterized with the midpoint ellipse algorithm; points outside the buffer
+    /// are clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(11, 11, Cell::default());
+    /// buf.draw_ellipse(5, 5, 3, 3, Cell::with_char('o'));
+    ///
+    /// for &(x, y) in &[(8, 5), (2, 5), (5, 8), (5, 2)] {
+    ///     assert_eq!(buf[(x, y)].ch(), 'o');
+    /// }
+    /// assert_eq!(buf[(5, 5)].ch(), ' ');
+    /// ```
+    pub fn draw_ellipse(&mut self, cx: usize, cy: usize, rx: usize, ry: usize, cell: Cell) {
+        let (cx, cy) = (cx as i64, cy as i64);
+        let mut plot = |x: i64, y: i64| {
+            let points = [(cx + x, cy + y), (cx - x, cy + y), (cx + x, cy - y), (cx - x, cy - y)];
+            for &(px, py) in &points {
+                if px >= 0 && py >= 0 {
+                    if let Some(c) = self.get_mut(px as usize, py as usize) {
+                        *c = cell;
+                    }
+                }
+            }
+        };
+
+        let (rx, ry) = (rx as i64, ry as i64);
+        if rx == 0 || ry == 0 {
+            // A degenerate ellipse is a straight line.
+            for x in 0..=rx {
+                for y in 0..=ry {
+                    plot(x, y);
+                }
+            }
+            return;
+        }
+
+        let (rx2, ry2) = ((rx * rx) as f64, (ry * ry) as f64);
+        let (mut x, mut y) = (0, ry);
+        let (mut dx, mut dy) = (0.0, 2.0 * rx2 * y as f64);
+
+        // Region 1, where the slope of the curve is shallower than -1.
+        let mut p = ry2 - rx2 * ry as f64 + rx2 / 4.0;
+        while dx < dy {
+            plot(x, y);
+            x += 1;
+            dx += 2.0 * ry2;
+            if p < 0.0 {
+                p += ry2 + dx;
+            } else {
+                y -= 1;
+                dy -= 2.0 * rx2;
+                p += ry2 + dx - dy;
+            }
+        }
+
+        // Region 2, where the slope is steeper.
+        let (fx, fy) = (x as f64 + 0.5, (y - 1) as f64);
+        let mut p = ry2 * fx * fx + rx2 * fy * fy - rx2 * ry2;
+        while y >= 0 {
+            plot(x, y);
+            y -= 1;
+            dy -= 2.0 * rx2;
+            if p > 0.0 {
+                p += rx2 - dy;
+            } else {
+                x += 1;
+                dx += 2.0 * ry2;
+                p += rx2 - dy + dx;
+            }
+        }
+    }
+
+    /// Returns a copy of `self` in which every cell that differs from the corresponding cell of
+    /// `other` is replaced with `marker`, making mismatches easy to spot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are of different sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let expected = CellBuffer::new(3, 2, Cell::with_char('a'));
+    /// let mut actual = expected.clone();
+    /// actual[(1, 0)].set_ch('b');
+    /// actual[(2, 1)].set_ch('c');
+    ///
+    /// let marked = actual.highlight_diff(&expected, Cell::with_char('!'));
+    /// let text: String = marked.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "a!aaa!");
+    /// ```
+    pub fn highlight_diff(&self, other: &CellBuffer, marker: Cell) -> CellBuffer {
+        let mut marked = self.clone();
+        for (x, y, _) in self.diff_iter(other) {
+            marked[(x, y)] = marker;
+        }
+        marked
+    }
+
+    /// Flattens a stack of layers into a new `cols`×`rows` buffer.
+    ///
+    /// Each `(layer, x, y)` is drawn in order with its top-left corner at `(x, y)`, so later layers
+    /// cover earlier ones. Cells holding the `transparent` character let the layers beneath show
+    /// through, and cells not covered by any layer are `Cell::default()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let background = CellBuffer::new(4, 2, Cell::with_char('.'));
+    /// let mut overlay = CellBuffer::new(3, 1, Cell::with_char('#'));
+    /// overlay[(1, 0)].set_ch(' ');
+    ///
+    /// let frame = CellBuffer::compose(&[(&background, 0, 0), (&overlay, 1, 1)], 4, 2, ' ');
+    /// let text: String = frame.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, ".....#.#");
+    /// ```
+    pub fn compose(layers: &[(&CellBuffer, usize, usize)],
+                   cols: usize,
+                   rows: usize,
+                   transparent: char)
+                   -> CellBuffer {
+        let mut composed = CellBuffer::new(cols, rows, Cell::default());
+        for &(layer, x, y) in layers {
+            composed.stamp(layer, x, y, transparent, None);
+        }
+        composed
+    }
+
+    /// Returns a hash of the cells of each row.
+    ///
+    /// Hashes are deterministic, so rows can be compared against those of a previous frame to
+    /// find the rows that changed. They are not guaranteed to be stable across Rust releases.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let a = CellBuffer::new(3, 3, Cell::default());
+    /// let mut b = a.clone();
+    /// assert_eq!(a.row_hashes(), b.row_hashes());
+    ///
+    /// b[(2, 1)].set_ch('x');
+    /// let (ha, hb) = (a.row_hashes(), b.row_hashes());
+    /// assert_eq!(ha[0], hb[0]);
+    /// assert!(ha[1] != hb[1]);
+    /// assert_eq!(ha[2], hb[2]);
+    /// ```
+    pub fn row_hashes(&self) -> Vec<u64> {
+        (0..self.rows)
+            .map(|y| {
+                let mut hasher = DefaultHasher::new();
+                self.buf[y * self.cols..(y + 1) * self.cols].hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    /// Returns `true` if every cell in row `y` is `Cell::default()`, or `false` if `y` is out of
+    /// bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(4, 4, Cell::default());
+    /// buf[(1, 0)].set_ch('a');
+    /// buf[(3, 1)].set_ch('b');
+    ///
+    /// assert!(!buf.row_is_blank(0));
+    /// assert!(!buf.row_is_blank(1));
+    /// assert!(buf.row_is_blank(2));
+    /// assert!(buf.row_is_blank(3));
+    /// assert!(!buf.row_is_blank(4));
+    /// assert_eq!(buf.last_nonblank_row(), Some(1));
+    /// ```
+    pub fn row_is_blank(&self, y: usize) -> bool {
+        if y >= self.rows {
+            return false;
+        }
+        let blank = Cell::default();
+        self.buf[y * self.cols..(y + 1) * self.cols].iter().all(|cell| *cell == blank)
+    }
+
+    /// Returns the index of the bottommost row that is not blank, or `None` if every row is.
+    pub fn last_nonblank_row(&self) -> Option<usize> {
+        (0..self.rows).rev().find(|&y| !self.row_is_blank(y))
+    }
+
+    /// Returns an estimate of the memory used by the buffer, in bytes.
+    ///
+    /// This is the size of the `CellBuffer` itself plus its backing storage (counted by capacity,
+    /// not length), the per-cell version stamps and any saved checkpoints.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::mem;
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(10, 10, Cell::default());
+    /// let size = buf.byte_size();
+    /// assert!(size >= 10 * 10 * mem::size_of::<Cell>());
+    ///
+    /// buf.reserve(100);
+    /// assert!(buf.byte_size() >= size + 100 * mem::size_of::<Cell>());
+    /// ```
+    pub fn byte_size(&self) -> usize {
+        let versions = self.versions.stamps.as_ref().map_or(0, |stamps| {
+            stamps.capacity() * mem::size_of::<u64>()
+        });
+        let history: usize = self.history
+            .snapshots
+            .iter()
+            .map(|(_, cells)| cells.capacity() * mem::size_of::<Cell>())
+            .sum();
+        mem::size_of::<CellBuffer>() + self.buf.capacity() * mem::size_of::<Cell>() + versions +
+        history
+    }
+
+    /// Returns the offset of the cell at `(x, y)` in the flat, row-major cell slice, or `None`
+    /// if the coordinates are out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let buf = CellBuffer::new(4, 3, Cell::default());
+    /// assert_eq!(buf.to_index(1, 2), Some(9));
+    /// assert_eq!(buf.from_index(9), Some((1, 2)));
+    /// assert_eq!(buf.to_index(4, 0), None);
+    /// assert_eq!(buf.to_index(0, 3), None);
+    /// assert_eq!(buf.from_index(12), None);
+    ///
+    /// for i in 0..buf.len() {
+    ///     let (x, y) = buf.from_index(i).unwrap();
+    ///     assert_eq!(buf.to_index(x, y), Some(i));
+    /// }
+    /// ```
+    pub fn to_index(&self, x: usize, y: usize) -> Option<usize> {
+        self.pos_to_index(x, y)
+    }
+
+    /// Returns the coordinates of the cell at offset `i` in the flat, row-major cell slice, or
+    /// `None` if the offset is out of bounds.
+    pub fn from_index(&self, i: usize) -> Option<Pos> {
+        if i < self.buf.len() {
+            Some((i % self.cols, i / self.cols))
+        } else {
+            None
+        }
+    }
+
+    /// Groups the cells that differ between `self` and `other` into at most `max_rects`
+    /// rectangles, returned as `(x, y, w, h)`.
+    ///
+    /// Changes that touch, including diagonally, always share a rectangle. If that still leaves
+    /// more than `max_rects` rectangles, the pairs whose union adds the fewest unchanged cells are
+    /// merged until it doesn't. A `max_rects` of zero is treated as one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are not the same size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let old = CellBuffer::new(12, 8, Cell::default());
+    /// let mut new = old.clone();
+    /// for &(x, y) in &[(1, 1), (2, 1), (1, 2), (8, 5), (9, 6)] {
+    ///     new[(x, y)].set_ch('#');
+    /// }
+    ///
+    /// assert_eq!(new.dirty_rects(&old, 4), vec![(1, 1, 2, 2), (8, 5, 2, 2)]);
+    /// assert_eq!(new.dirty_rects(&old, 1), vec![(1, 1, 9, 6)]);
+    /// assert_eq!(old.dirty_rects(&old, 4), vec![]);
+    /// ```
+    pub fn dirty_rects(&self,
+                       other: &CellBuffer,
+                       max_rects: usize)
+                       -> Vec<(usize, usize, usize, usize)> {
+        // Rectangles are kept as (x0, y0, x1, y1) with exclusive ends while merging.
+        let mut rects: Vec<(usize, usize, usize, usize)> = Vec::new();
+        for (x, y, _) in self.diff_iter(other) {
+            match rects.last_mut() {
+                Some(last) if last.1 == y && last.2 == x => last.2 += 1,
+                _ => rects.push((x, y, x + 1, y + 1)),
+            }
+        }
+
+        merge_touching_rects(&mut rects);
+        while rects.len() > cmp::max(max_rects, 1) {
+            let mut best = (usize::MAX, 0, 0);
+            for i in 0..rects.len() {
+                for j in i + 1..rects.len() {
+                    let (a, b) = (rects[i], rects[j]);
+                    let cost = rect_area(rect_union(a, b)) - rect_area(a) - rect_area(b);
+                    if cost < best.0 {
+                        best = (cost, i, j);
+                    }
+                }
+            }
+            let (_, i, j) = best;
+            rects[i] = rect_union(rects[i], rects[j]);
+            rects.remove(j);
+            merge_touching_rects(&mut rects);
+        }
+
+        rects.into_iter().map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0, y1 - y0)).collect()
+    }
+
+    /// Reflects the top-left quadrant of the buffer into the other three: flipped horizontally
+    /// into the top-right, vertically into the bottom-left and both ways into the bottom-right.
+    ///
+    /// With an odd number of columns or rows the center column or row belongs to the top-left
+    /// quadrant and is shared by both halves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(4, 4, Cell::default());
+    /// buf[(0, 0)].set_ch('a');
+    /// buf[(1, 0)].set_ch('b');
+    /// buf[(0, 1)].set_ch('c');
+    /// buf[(1, 1)].set_ch('d');
+    /// buf.mirror_quadrants();
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "abbacddccddcabba");
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)].set_ch('a');
+    /// buf[(1, 0)].set_ch('b');
+    /// buf.mirror_quadrants();
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "aba");
+    /// ```
+    pub fn mirror_quadrants(&mut self) {
+        self.touch_all();
+        let (cols, rows) = (self.cols, self.rows);
+        for y in 0..rows {
+            for x in 0..cols {
+                let (sx, sy) = (cmp::min(x, cols - 1 - x), cmp::min(y, rows - 1 - y));
+                if (sx, sy) != (x, y) {
+                    self.buf[y * cols + x] = self.buf[sy * cols + sx];
+                }
+            }
+        }
+    }
+
+    /// Returns the characters of the buffer, one `Vec` per row.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Color, Attr};
+    ///
+    /// let (fg, bg) = (Color::Default, Color::Default);
+    ///
+    /// let grid = vec![vec!['a', 'b', 'c'], vec!['d', 'e', 'f']];
+    /// let buf = CellBuffer::from_char_grid(&grid, fg, bg, Attr::Default);
+    /// assert_eq!(buf.char_grid(), grid);
+    ///
+    /// let ragged = vec![vec!['a'], vec!['b', 'c']];
+    /// let buf = CellBuffer::from_char_grid(&ragged, fg, bg, Attr::Default);
+    /// assert_eq!(buf.char_grid(), vec![vec!['a', ' '], vec!['b', 'c']]);
+    /// ```
+    pub fn char_grid(&self) -> Vec<Vec<char>> {
+        (0..self.rows)
+            .map(|y| self.buf[y * self.cols..(y + 1) * self.cols].iter().map(|c| c.ch()).collect())
+            .collect()
+    }
+
+    /// Constructs a buffer from rows of characters, all given the same style.
+    ///
+    /// The buffer is as wide as the longest row; shorter rows are padded with blank cells of the
+    /// same style.
+    pub fn from_char_grid(grid: &[Vec<char>], fg: Color, bg: Color, attrs: Attr) -> CellBuffer {
+        let cols = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut buf = CellBuffer::new(cols, grid.len(), Cell::new(' ', fg, bg, attrs));
+        for (y, row) in grid.iter().enumerate() {
+            for (x, &ch) in row.iter().enumerate() {
+                buf.buf[y * cols + x].set_ch(ch);
+            }
+        }
+        buf
+    }
+
+    /// Returns the fraction of cells that differ between `self` and `other`, from `0.0` for
+    /// identical buffers to `1.0` when every cell differs. Empty buffers return `0.0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are not the same size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let old = CellBuffer::new(4, 4, Cell::default());
+    /// let mut new = old.clone();
+    /// assert_eq!(new.diff_ratio(&old), 0.0);
+    ///
+    /// for x in 0..4 {
+    ///     new[(x, 0)].set_ch('#');
+    /// }
+    /// assert_eq!(new.diff_ratio(&old), 0.25);
+    ///
+    /// let full = CellBuffer::new(4, 4, Cell::with_char('#'));
+    /// assert_eq!(full.diff_ratio(&old), 1.0);
+    /// ```
+    pub fn diff_ratio(&self, other: &CellBuffer) -> f32 {
+        let changed = self.diff_iter(other).count();
+        if self.buf.is_empty() {
+            0.0
+        } else {
+            changed as f32 / self.buf.len() as f32
+        }
+    }
+
+    /// Recolors the foreground of each cell in the `w`×`h` region with its top-left corner at
+    /// `(x, y)` to the hue and saturation of `target`, keeping the cell's own lightness.
+    ///
+    /// The new colors are `Color::Rgb`. Cells whose foreground is `Color::Default` are left
+    /// alone, as is the whole region if `target` is `Color::Default`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)].set_fg(Color::Rgb(40, 40, 40));
+    /// buf[(1, 0)].set_fg(Color::Rgb(220, 220, 220));
+    /// buf.tint_region(0, 0, 3, 1, Color::Blue);
+    ///
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Rgb(0, 0, 80));
+    /// assert_eq!(buf[(1, 0)].fg(), Color::Rgb(185, 185, 255));
+    /// assert_eq!(buf[(2, 0)].fg(), Color::Default);
+    /// ```
+    pub fn tint_region(&mut self, x: usize, y: usize, w: usize, h: usize, target: Color) {
+        let (hue, saturation, _) = match target.to_rgb() {
+            Some(rgb) => rgb_to_hsl(rgb),
+            None => return,
+        };
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        for iy in y..y + h {
+            for ix in x..x + w {
+                let cell = &mut self[(ix, iy)];
+                if let Some(rgb) = cell.fg().to_rgb() {
+                    let (_, _, lightness) = rgb_to_hsl(rgb);
+                    let (r, g, b) = hsl_to_rgb(hue, saturation, lightness);
+                    cell.set_fg(Color::Rgb(r, g, b));
+                }
+            }
+        }
+    }
+
+    /// Draws a one-row sparkline of `values` along row `y`, starting at column `x` and spanning
+    /// at most `width` columns.
+    ///
+    /// Each value is scaled between the smallest and largest value and drawn as one of the block
+    /// glyphs `▁` through `█`; if all values are equal they are drawn as `▁`. When there are more
+    /// values than columns they are sampled evenly, and when there are fewer the sparkline is
+    /// only as wide as the number of values. Cells outside the buffer are clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color};
+    ///
+    /// let mut buf = CellBuffer::new(8, 1, Cell::default());
+    /// let ramp: Vec<f32> = (0..8).map(|v| v as f32).collect();
+    /// buf.draw_sparkline(0, 0, 8, &ramp, Color::Green, Color::Default);
+    ///
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "▁▂▃▄▅▆▇█");
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Green);
+    ///
+    /// // Sixteen values sampled into four columns.
+    /// let ramp: Vec<f32> = (0..16).map(|v| v as f32).collect();
+    /// buf.draw_sparkline(0, 0, 4, &ramp, Color::Green, Color::Default);
+    /// let text: String = buf.iter().take(4).map(|c| c.ch()).collect();
+    /// assert_eq!(text, "▁▃▅▇");
+    ///
+    /// buf.draw_sparkline(usize::MAX, 0, 8, &ramp, Color::Red, Color::Default);
+    /// assert!(buf.iter().all(|c| c.fg() == Color::Green));
+    /// ```
+    pub fn draw_sparkline(&mut self,
+                          x: usize,
+                          y: usize,
+                          width: usize,
+                          values: &[f32],
+                          fg: Color,
+                          bg: Color) {
+        let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let cols = width.min(values.len());
+        for i in 0..cols {
+            let value = values[i * values.len() / cols];
+            let level = if max > min {
+                ((value - min) / (max - min) * 7.0).round() as usize
+            } else {
+                0
+            };
+            match self.get_mut(x.saturating_add(i), y) {
+                Some(cell) => *cell = Cell::new(BLOCK_GLYPHS[level + 1], fg, bg, Attr::Default),
+                None => break,
+            }
+        }
+    }
+
+    /// Releases any backing storage beyond what the current cells need, such as after shrinking
+    /// the buffer with `resize`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(10, 10, Cell::default());
+    /// buf.resize(200, 100, Cell::default());
+    /// buf.resize(10, 10, Cell::default());
+    /// assert!(buf.capacity() >= 200 * 100);
+    ///
+    /// buf.shrink_to_fit();
+    /// assert!(buf.capacity() < 200 * 100);
+    /// assert!(buf.capacity() >= 10 * 10);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.buf.shrink_to_fit();
+        if let Some(ref mut stamps) = self.versions.stamps {
+            stamps.shrink_to_fit();
+        }
+    }
+
+    /// Scrolls the whole buffer up by one row, filling the bottom row with `blank`, and writes `s`
+    /// into the bottom row using the given style.
+    ///
+    /// Text beyond the right edge of the buffer is clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut log = CellBuffer::new(4, 2, Cell::default());
+    /// for line in &["one", "two", "three"] {
+    ///     log.append_line(line, Color::Default, Color::Default, Attr::Default, Cell::default());
+    /// }
+    ///
+    /// let text: String = log.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, "two thre");
+    /// ```
+    pub fn append_line(&mut self, s: &str, fg: Color, bg: Color, attrs: Attr, blank: Cell) {
+        if self.rows == 0 {
+            return;
+        }
+        let (cols, rows) = (self.cols, self.rows);
+        self.scroll_region_up(0, 0, cols, rows, 1, blank);
+        self.write_segments(0, rows - 1, &[(s, fg, bg, attrs)]);
+    }
+
+    /// Returns the number of terminal columns the characters in row `y` take up when displayed,
+    /// or `None` if `y` is out of bounds.
+    ///
+    /// Widths follow Unicode (see the `unicode-width` crate): East Asian wide and fullwidth
+    /// characters and emoji count as two columns, combining marks and control characters as
+    /// none, and everything else as one. Cells are not linked to one another, so a
+    /// wide character doesn't hide the cell after it; a result greater than the number of columns
+    /// means the row will overflow when printed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(6, 1, Cell::default());
+    /// buf[(0, 0)].set_ch('a');
+    /// buf[(1, 0)].set_ch('漢');
+    /// buf[(2, 0)].set_ch('字');
+    /// buf[(3, 0)].set_ch('Ｚ');
+    ///
+    /// assert_eq!(buf.row_display_width(0), Some(9));
+    /// assert_eq!(buf.row_display_width(1), None);
+    ///
+    /// // U+0941 is a combining vowel sign.
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)].set_ch('🚀');
+    /// buf[(1, 0)].set_ch('🛸');
+    /// buf[(2, 0)].set_ch('\u{941}');
+    /// assert_eq!(buf.row_display_width(0), Some(4));
+    /// ```
+    pub fn row_display_width(&self, y: usize) -> Option<usize> {
+        if y >= self.rows {
+            return None;
+        }
+        let row = &self.buf[y * self.cols..(y + 1) * self.cols];
+        Some(row.iter().map(|c| c.ch().width().unwrap_or(0)).sum())
+    }
+
+    /// Writes the lines of `text` into the `w`×`h` region with its top-left corner at `(x, y)`,
+    /// one line per row, using the given style.
+    ///
+    /// Lines are clipped to `w` columns, lines beyond the first `h` are dropped, and region cells
+    /// not covered by text are left untouched, as are any cells outside the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(5, 3, Cell::with_char('.'));
+    /// buf.fill_region_text(1, 0, 3, 2, "ab\ncdefg\nhij", Color::Default, Color::Default,
+    ///                      Attr::Default);
+    ///
+    /// let text: String = buf.iter().map(|c| c.ch()).collect();
+    /// assert_eq!(text, ".ab...cde......");
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_region_text(&mut self,
+                            x: usize,
+                            y: usize,
+                            w: usize,
+                            h: usize,
+                            text: &str,
+                            fg: Color,
+                            bg: Color,
+                            attrs: Attr) {
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        for (iy, line) in (y..y + h).zip(text.split('\n')) {
+            for (ix, ch) in (x..x + w).zip(line.chars()) {
+                self[(ix, iy)] = Cell::new(ch, fg, bg, attrs);
+            }
+        }
+    }
+
+    /// Saves a copy of the current size and cells, to be restored later with `rollback`.
+    ///
+    /// At most 8 checkpoints are kept unless changed with `set_history_depth`; once the limit is
+    /// reached, the oldest is dropped to make room. Clones of the buffer don't share its
+    /// checkpoints.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, HasSize};
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf.checkpoint();
+    /// buf[(0, 0)].set_ch('a');
+    /// buf.checkpoint();
+    /// buf.resize(5, 1, Cell::with_char('b'));
+    ///
+    /// assert!(buf.rollback());
+    /// assert_eq!(buf.size(), (3, 1));
+    /// assert_eq!(buf[(0, 0)].ch(), 'a');
+    ///
+    /// buf.checkpoint();
+    /// assert!(!buf.clone().rollback());
+    ///
+    /// assert!(buf.rollback());
+    /// assert!(buf.rollback());
+    /// assert_eq!(buf, CellBuffer::new(3, 1, Cell::default()));
+    /// assert!(!buf.rollback());
+    /// ```
+    pub fn checkpoint(&mut self) {
+        let history = &mut self.history;
+        if history.depth == 0 {
+            return;
+        }
+        if history.snapshots.len() == history.depth {
+            history.snapshots.pop_front();
+        }
+        history.snapshots.push_back(((self.cols, self.rows), self.buf.clone()));
+    }
+
+    /// Restores the most recent checkpoint and removes it from the history.
+    ///
+    /// Returns `false`, leaving the buffer untouched, if there are no checkpoints.
+    pub fn rollback(&mut self) -> bool {
+        match self.history.snapshots.pop_back() {
+            Some(((cols, rows), buf)) => {
+                self.cols = cols;
+                self.rows = rows;
+                self.buf = buf;
+                self.touch_all();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Discards all checkpoints.
+    pub fn clear_history(&mut self) {
+        self.history.snapshots.clear();
+    }
+
+    /// Sets the maximum number of checkpoints to keep, dropping the oldest ones if there are
+    /// already more than `depth`. A depth of zero disables checkpoints.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(1, 1, Cell::default());
+    /// buf.set_history_depth(2);
+    /// for ch in "abc".chars() {
+    ///     buf[(0, 0)].set_ch(ch);
+    ///     buf.checkpoint();
+    /// }
+    ///
+    /// assert!(buf.rollback());
+    /// assert_eq!(buf[(0, 0)].ch(), 'c');
+    /// assert!(buf.rollback());
+    /// assert_eq!(buf[(0, 0)].ch(), 'b');
+    /// assert!(!buf.rollback());
+    /// ```
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history.depth = depth;
+        while self.history.snapshots.len() > depth {
+            self.history.snapshots.pop_front();
+        }
+    }
+
+    /// Converts a `width`×`height` image into `out_cols`×`out_rows` cells of ASCII art.
+    ///
+    /// `pixels` holds the image row by row as RGBA bytes; the alpha channel is ignored. Each cell
+    /// covers a block of pixels, gets the block's average color as its foreground, and a character
+    /// from `ramp` picked by the block's average luminance, with the first character of `ramp`
+    /// used for black and the last for white. Cells get a space if `ramp` is empty or the image has
+    /// no pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` is shorter than `width * height * 4` bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Color};
+    ///
+    /// let white = [255u8, 255, 255, 255].repeat(8 * 4);
+    /// let buf = CellBuffer::from_rgba_image(8, 4, &white, 4, 2, " .:#");
+    /// assert!(buf.iter().all(|c| c.ch() == '#' && c.fg() == Color::Rgb(255, 255, 255)));
+    ///
+    /// // The left half is black, the right half a dark gray.
+    /// let mut pixels = Vec::new();
+    /// for _ in 0..4 {
+    ///     pixels.extend([0u8, 0, 0, 255].repeat(4));
+    ///     pixels.extend([100u8, 100, 100, 255].repeat(4));
+    /// }
+    /// let buf = CellBuffer::from_rgba_image(8, 4, &pixels, 2, 1, " .:#");
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), " .");
+    /// assert_eq!(buf[(1, 0)].fg(), Color::Rgb(100, 100, 100));
+    /// ```
+    pub fn from_rgba_image(width: usize,
+                           height: usize,
+                           pixels: &[u8],
+                           out_cols: usize,
+                           out_rows: usize,
+                           ramp: &str)
+                           -> CellBuffer {
+        assert!(pixels.len() >= width * height * 4, "not enough pixel data");
+        let ramp: Vec<char> = ramp.chars().collect();
+        let mut buf = CellBuffer::new(out_cols, out_rows, Cell::default());
+        // Blocks can round down to nothing when the output is larger than the image, so each
+        // covers at least one pixel.
+        let span = |i: usize, len: usize, out: usize| {
+            let start = i * len / out;
+            (start, ((i + 1) * len / out).max(start + 1).min(len))
+        };
+        for cy in 0..out_rows {
+            let (top, bottom) = span(cy, height, out_rows);
+            for cx in 0..out_cols {
+                let (left, right) = span(cx, width, out_cols);
+                let mut sum = [0usize; 3];
+                let mut count = 0;
+                for py in top..bottom {
+                    for px in left..right {
+                        let i = (py * width + px) * 4;
+                        for (total, &channel) in sum.iter_mut().zip(&pixels[i..i + 3]) {
+                            *total += channel as usize;
+                        }
+                        count += 1;
+                    }
+                }
+                if count == 0 || ramp.is_empty() {
+                    continue;
+                }
+                let [r, g, b] = sum.map(|total| (total / count) as u8);
+                let luminance = (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) / 255.0;
+                let ch = ramp[(luminance * (ramp.len() - 1) as f32).round() as usize];
+                buf.buf[cy * out_cols + cx] =
+                    Cell::new(ch, Color::Rgb(r, g, b), Color::Default, Attr::Default);
+            }
+        }
+        buf
+    }
+
+    /// Returns a copy of the cells in row `y`, or `None` if `y` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(3, 2, Cell::default());
+    /// let mut row = buf.row_owned(1).unwrap();
+    /// row[0].set_ch('a');
+    /// assert_eq!(buf[(0, 1)].ch(), ' ');
+    ///
+    /// buf.set_row(1, &row);
+    /// assert_eq!(buf[(0, 1)].ch(), 'a');
+    /// assert_eq!(buf.row_owned(2), None);
+    /// ```
+    pub fn row_owned(&self, y: usize) -> Option<Vec<Cell>> {
+        if y < self.rows {
+            Some(self.buf[y * self.cols..(y + 1) * self.cols].to_vec())
+        } else {
+            None
+        }
+    }
+
+    /// Overwrites row `y`, starting from its first column, with `cells`.
+    ///
+    /// Cells beyond the width of the buffer are ignored, as is a `y` out of bounds. If `cells` is
+    /// shorter than a row, the rest of the row is untouched.
+    pub fn set_row(&mut self, y: usize, cells: &[Cell]) {
+        if y >= self.rows {
+            return;
+        }
+        let n = cells.len().min(self.cols);
+        let start = y * self.cols;
+        self.buf[start..start + n].copy_from_slice(&cells[..n]);
+        self.versions.touch_range(start..start + n);
+    }
+
+    /// Sets the color of the top (`top` is `true`) or bottom half of the cell at `(x, y)`,
+    /// treating each cell as two vertically stacked sub-pixels.
+    ///
+    /// The cell is drawn as `▀` with the top half's color as its foreground and the bottom half's
+    /// as its background. The other half keeps its color: existing `▀`, `▄` and `█` cells are
+    /// read back as half-block pixels, and any other cell as two pixels of its background color.
+    /// Coordinates out of bounds are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color};
+    ///
+    /// let mut buf = CellBuffer::new(2, 1, Cell::default());
+    /// buf.set_subpixel(0, 0, true, Color::Red);
+    /// assert_eq!((buf[(0, 0)].ch(), buf[(0, 0)].fg(), buf[(0, 0)].bg()),
+    ///            ('▀', Color::Red, Color::Default));
+    ///
+    /// buf.set_subpixel(0, 0, false, Color::Blue);
+    /// assert_eq!((buf[(0, 0)].ch(), buf[(0, 0)].fg(), buf[(0, 0)].bg()),
+    ///            ('▀', Color::Red, Color::Blue));
+    /// ```
+    pub fn set_subpixel(&mut self, x: usize, y: usize, top: bool, color: Color) {
+        if let Some(cell) = self.get_mut(x, y) {
+            let (upper, lower) = match cell.ch() {
+                '▀' => (cell.fg(), cell.bg()),
+                '▄' => (cell.bg(), cell.fg()),
+                '█' => (cell.fg(), cell.fg()),
+                _ => (cell.bg(), cell.bg()),
+            };
+            let (upper, lower) = if top { (color, lower) } else { (upper, color) };
+            cell.set_ch('▀');
+            cell.set_fg(upper);
+            cell.set_bg(lower);
+        }
+    }
+
+    /// Compares two buffers, treating `Color::Default` and `Attr::Default` in either one as
+    /// matching any color or attributes in the other.
+    ///
+    /// Characters must always match, and buffers of different sizes are never equal. This is
+    /// meant for tests whose expected output doesn't care about styling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let expected = CellBuffer::new(2, 1, Cell::with_char('x'));
+    /// let red = Cell::new('x', Color::Red, Color::Black, Attr::Bold);
+    /// let mut actual = CellBuffer::new(2, 1, red);
+    /// assert!(actual.eq_ignoring_default(&expected));
+    /// assert!(expected.eq_ignoring_default(&actual));
+    ///
+    /// let blue = Cell::new('x', Color::Blue, Color::Default, Attr::Default);
+    /// assert!(!CellBuffer::new(2, 1, blue).eq_ignoring_default(&actual));
+    ///
+    /// actual[(1, 0)].set_ch('y');
+    /// assert!(!actual.eq_ignoring_default(&expected));
+    /// ```
+    pub fn eq_ignoring_default(&self, other: &CellBuffer) -> bool {
+        let color_eq = |a: Color, b: Color| a == b || a == Color::Default || b == Color::Default;
+        self.size() == other.size() &&
+        self.buf.iter().zip(other.buf.iter()).all(|(a, b)| {
+            a.ch() == b.ch() && color_eq(a.fg(), b.fg()) && color_eq(a.bg(), b.bg()) &&
+            (a.attrs() == b.attrs() || a.attrs() == Attr::Default || b.attrs() == Attr::Default)
+        })
+    }
+
+    /// Draws a table with its top-left corner at `(x, y)`, with one column per entry of
+    /// `col_widths` and one row per entry of `rows`.
+    ///
+    /// Every cell is surrounded by lines in the given `style`, so the table is
+    /// `col_widths.iter().sum() + col_widths.len() + 1` columns wide and `2 * rows.len() + 1` rows
+    /// tall. Each string is written left-aligned in its cell and truncated to the column width;
+    /// strings beyond the number of columns are ignored. Nothing is drawn if there are no columns
+    /// or no rows, and parts of the table outside the buffer are clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, BorderStyle};
+    ///
+    /// let mut buf = CellBuffer::new(8, 5, Cell::default());
+    /// let rows = vec![vec!["ab", "c"], vec!["defg", "hi"]];
+    /// buf.draw_table(0, 0, &[3, 2], &rows, BorderStyle::Single, Color::Default, Color::Default);
+    ///
+    /// let lines: Vec<String> = buf.char_grid().iter().map(|row| row.iter().collect()).collect();
+    /// assert_eq!(lines, ["┌───┬──┐",
+    ///                    "│ab │c │",
+    ///                    "├───┼──┤",
+    ///                    "│def│hi│",
+    ///                    "└───┴──┘"]);
+    ///
+    /// // Tables reaching past the buffer are clipped.
+    /// buf.draw_table(6, 3, &[usize::MAX], &rows, BorderStyle::Double, Color::Red, Color::Default);
+    /// assert_eq!(buf[(6, 3)].ch(), '╔');
+    /// assert_eq!(buf[(7, 4)].ch(), 'a');
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_table(&mut self,
+                      x: usize,
+                      y: usize,
+                      col_widths: &[usize],
+                      rows: &[Vec<&str>],
+                      style: BorderStyle,
+                      fg: Color,
+                      bg: Color) {
+        if col_widths.is_empty() || rows.is_empty() {
+            return;
+        }
+        let (horizontal, vertical, junctions) = style.glyphs();
+        // The columns of the vertical lines, relative to `x`, in increasing order.
+        let mut lines = vec![0usize];
+        for &width in col_widths {
+            let last = lines[lines.len() - 1];
+            lines.push(last.saturating_add(width).saturating_add(1));
+        }
+        let width = lines[lines.len() - 1].saturating_add(1);
+        let height = 2 * rows.len() + 1;
+        // Picks the top/left, middle or bottom/right junction for a position along an edge.
+        let edge = |i: usize, len: usize| if i == 0 { 0 } else if i == len - 1 { 2 } else { 1 };
+
+        let (_, _, visible_cols, visible_rows) = self.clamp_rect(x, y, width, height);
+        for iy in 0..visible_rows {
+            for ix in 0..visible_cols {
+                let ch = match (iy % 2 == 0, lines.binary_search(&ix).is_ok()) {
+                    (true, true) => junctions[edge(iy, height)][edge(ix, width)],
+                    (true, false) => horizontal,
+                    (false, true) => vertical,
+                    (false, false) => ' ',
+                };
+                self[(x + ix, y + iy)] = Cell::new(ch, fg, bg, Attr::Default);
+            }
+        }
+
+        for (r, row) in rows.iter().enumerate().take(visible_rows / 2) {
+            for ((&text, &width), &line) in row.iter().zip(col_widths).zip(&lines) {
+                let left = x.saturating_add(line).saturating_add(1);
+                for (i, ch) in text.chars().take(width).enumerate() {
+                    match self.get_mut(left.saturating_add(i), y + 2 * r + 1) {
+                        Some(cell) => cell.set_ch(ch),
+                        None => break,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Replaces the attributes of every cell with the result of calling `f` on them, such as to
+    /// downgrade attributes the terminal doesn't support.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)].set_attrs(Attr::Bold);
+    /// buf[(1, 0)].set_attrs(Attr::BoldUnderline);
+    /// buf[(2, 0)].set_attrs(Attr::Reverse);
+    ///
+    /// buf.remap_attrs(|attrs| Attr::from_bits(attrs.bits() & !Attr::Bold.bits()).unwrap());
+    /// let attrs: Vec<Attr> = buf.iter().map(|c| c.attrs()).collect();
+    /// assert_eq!(attrs, [Attr::Default, Attr::Underline, Attr::Reverse]);
+    /// ```
+    pub fn remap_attrs<F: Fn(Attr) -> Attr>(&mut self, f: F) {
+        self.touch_all();
+        for cell in self.buf.iter_mut() {
+            let attrs = f(cell.attrs());
+            cell.set_attrs(attrs);
+        }
+    }
+
+    /// Returns the part of the `w`×`h` rectangle with its top-left corner at `(x, y)` that lies
+    /// inside the buffer, as `(x, y, w, h)`.
+    ///
+    /// The origin is returned unchanged and the width and height are reduced to fit. If the
+    /// rectangle and the buffer don't overlap, both the width and the height are zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let buf = CellBuffer::new(10, 5, Cell::default());
+    /// assert_eq!(buf.clamp_rect(2, 1, 3, 3), (2, 1, 3, 3));
+    /// assert_eq!(buf.clamp_rect(8, 3, 5, 5), (8, 3, 2, 2));
+    /// assert_eq!(buf.clamp_rect(12, 0, 5, 5), (12, 0, 0, 0));
+    /// assert_eq!(buf.clamp_rect(0, 7, 5, 5), (0, 7, 0, 0));
+    /// assert_eq!(buf.clamp_rect(2, 1, 0, 3), (2, 1, 0, 0));
+    /// assert_eq!(buf.clamp_rect(0, 0, usize::MAX, usize::MAX), (0, 0, 10, 5));
+    /// ```
+    pub fn clamp_rect(&self,
+                      x: usize,
+                      y: usize,
+                      w: usize,
+                      h: usize)
+                      -> (usize, usize, usize, usize) {
+        let (w, h) = (w.min(self.cols.saturating_sub(x)), h.min(self.rows.saturating_sub(y)));
+        if w == 0 || h == 0 {
+            (x, y, 0, 0)
+        } else {
+            (x, y, w, h)
+        }
+    }
+
+    /// Calls `update` on every cell for which `pred` returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(4, 1, Cell::default());
+    /// for (cell, ch) in buf.iter_mut().zip("a1b2".chars()) {
+    ///     cell.set_ch(ch);
+    /// }
+    ///
+    /// buf.paint_if(|c| c.ch().is_ascii_digit(), |c| { c.set_attrs(Attr::Bold); });
+    /// let attrs: Vec<Attr> = buf.iter().map(|c| c.attrs()).collect();
+    /// assert_eq!(attrs, [Attr::Default, Attr::Bold, Attr::Default, Attr::Bold]);
+    /// ```
+    pub fn paint_if<F, G>(&mut self, pred: F, update: G)
+        where F: Fn(&Cell) -> bool,
+              G: Fn(&mut Cell)
+    {
+        self.touch_all();
+        for cell in self.buf.iter_mut().filter(|c| pred(c)) {
+            update(cell);
+        }
+    }
+
+    /// Returns the characters of row `y` encoded as UTF-8, without styling or a trailing newline,
+    /// or `None` if `y` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(3, 2, Cell::default());
+    /// buf[(0, 1)].set_ch('é');
+    /// buf[(1, 1)].set_ch('x');
+    ///
+    /// let bytes = buf.row_bytes(1).unwrap();
+    /// assert_eq!(bytes.len(), 4);
+    /// assert_eq!(String::from_utf8(bytes).unwrap(), "éx ");
+    /// assert_eq!(buf.row_bytes(2), None);
+    /// ```
+    pub fn row_bytes(&self, y: usize) -> Option<Vec<u8>> {
+        if y >= self.rows {
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(self.cols);
+        let mut utf8 = [0; 4];
+        for cell in &self.buf[y * self.cols..(y + 1) * self.cols] {
+            bytes.extend_from_slice(cell.ch().encode_utf8(&mut utf8).as_bytes());
+        }
+        Some(bytes)
+    }
+
+    /// Writes `s` along row `y` starting at column `x` using the given style, expanding each tab
+    /// into blanks up to the next multiple of `tab_width` columns from `x`.
+    ///
+    /// A `tab_width` of zero drops tabs. Writing stops at the right edge of the buffer. Returns
+    /// the number of columns written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(8, 1, Cell::with_char('.'));
+    /// let written = buf.write_str_tabs(1, 0, "a\tb\tc", 4, Color::Default, Color::Default,
+    ///                                  Attr::Default);
+    ///
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), ".a   b  ");
+    /// assert_eq!(buf[(5, 0)].ch(), 'b');
+    /// assert_eq!(written, 7);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_str_tabs(&mut self,
+                          x: usize,
+                          y: usize,
+                          s: &str,
+                          tab_width: usize,
+                          fg: Color,
+                          bg: Color,
+                          attrs: Attr)
+                          -> usize {
+        if y >= self.rows || x >= self.cols {
+            return 0;
+        }
+        let mut col = 0;
+        for ch in s.chars() {
+            let (ch, width) = match ch {
+                '\t' if tab_width == 0 => continue,
+                '\t' => (' ', tab_width - col % tab_width),
+                _ => (ch, 1),
+            };
+            for _ in 0..width {
+                if x + col >= self.cols {
+                    return col;
+                }
+                self[(x + col, y)] = Cell::new(ch, fg, bg, attrs);
+                col += 1;
+            }
+        }
+        col
+    }
+
+    /// Returns an iterator over mutable references to the cells whose foreground color is `fg`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)].set_fg(Color::Red);
+    /// buf[(2, 0)].set_fg(Color::Red);
+    ///
+    /// for cell in buf.cells_with_fg_mut(Color::Red) {
+    ///     cell.set_attrs(Attr::Bold);
+    /// }
+    /// let attrs: Vec<Attr> = buf.iter().map(|c| c.attrs()).collect();
+    /// assert_eq!(attrs, [Attr::Bold, Attr::Default, Attr::Bold]);
+    /// ```
+    pub fn cells_with_fg_mut(&mut self, fg: Color) -> impl Iterator<Item = &mut Cell> + '_ {
+        self.touch_all();
+        self.buf.iter_mut().filter(move |cell| cell.fg() == fg)
+    }
+
+    /// Returns a hash of the buffer's size and every cell outside the `w`×`h` rectangle with its
+    /// top-left corner at `(x, y)`.
+    ///
+    /// Like `row_hashes`, the hash is deterministic but not guaranteed to be stable across Rust
+    /// releases.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(6, 4, Cell::default());
+    /// let hash = buf.hash_excluding(1, 1, 2, 2);
+    ///
+    /// buf[(2, 2)].set_ch('x');
+    /// assert_eq!(buf.hash_excluding(1, 1, 2, 2), hash);
+    ///
+    /// buf[(3, 2)].set_ch('x');
+    /// assert!(buf.hash_excluding(1, 1, 2, 2) != hash);
+    /// ```
+    pub fn hash_excluding(&self, x: usize, y: usize, w: usize, h: usize) -> u64 {
+        let (x, y, w, h) = self.clamp_rect(x, y, w, h);
+        let mut hasher = DefaultHasher::new();
+        self.size().hash(&mut hasher);
+        for (i, cell) in self.buf.iter().enumerate() {
+            let (ix, iy) = (i % self.cols, i / self.cols);
+            if ix < x || ix >= x + w || iy < y || iy >= y + h {
+                cell.hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Returns a frame between `a` and `b`, where a `t` of 0.0 yields `a` and 1.0 yields `b`.
+    ///
+    /// The colors of each cell are blended with `Color::blend`; the character and attributes are
+    /// taken from `a` while `t` is below 0.5 and from `b` after that.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are not the same size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let a = CellBuffer::new(2, 1, Cell::new('a', Color::Rgb(0, 0, 0), Color::Rgb(0, 0, 0),
+    ///                                         Attr::Default));
+    /// let b = CellBuffer::new(2, 1, Cell::new('b', Color::Rgb(200, 100, 0),
+    ///                                         Color::Rgb(255, 255, 255), Attr::Bold));
+    ///
+    /// assert_eq!(CellBuffer::interpolate(&a, &b, 0.0), a);
+    /// assert_eq!(CellBuffer::interpolate(&a, &b, 1.0), b);
+    ///
+    /// let mid = CellBuffer::interpolate(&a, &b, 0.5);
+    /// assert_eq!(mid[(0, 0)], Cell::new('b', Color::Rgb(100, 50, 0), Color::Rgb(128, 128, 128),
+    ///                                   Attr::Bold));
+    /// ```
+    pub fn interpolate(a: &CellBuffer, b: &CellBuffer, t: f32) -> CellBuffer {
+        assert_eq!(a.size(), b.size(), "buffers must be the same size");
+        let buf = a.buf
+            .iter()
+            .zip(b.buf.iter())
+            .map(|(ca, cb)| {
+                let base = if t < 0.5 { ca } else { cb };
+                Cell::new(base.ch(), ca.fg().blend(cb.fg(), t), ca.bg().blend(cb.bg(), t),
+                          base.attrs())
+            })
+            .collect();
+        CellBuffer::from_vec(a.cols, a.rows, buf)
+    }
+
+    /// Writes `s` along row `y` so that its last character lands at column `right_x` and the
+    /// others extend to the left, using the given style.
+    ///
+    /// Characters that would fall left of column 0 or outside the buffer are clipped. Returns the
+    /// number of cells written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(8, 1, Cell::with_char('.'));
+    /// let written = buf.write_str_rtl(5, 0, "word", Color::Default, Color::Default,
+    ///                                 Attr::Default);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "..word..");
+    /// assert_eq!(written, 4);
+    ///
+    /// let written = buf.write_str_rtl(1, 0, "abc", Color::Default, Color::Default,
+    ///                                 Attr::Default);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "bcword..");
+    /// assert_eq!(written, 2);
+    /// ```
+    pub fn write_str_rtl(&mut self,
+                         right_x: usize,
+                         y: usize,
+                         s: &str,
+                         fg: Color,
+                         bg: Color,
+                         attrs: Attr)
+                         -> usize {
+        let mut written = 0;
+        for (offset, ch) in s.chars().rev().enumerate() {
+            if offset > right_x {
+                break;
+            }
+            if let Some(cell) = self.get_mut(right_x - offset, y) {
+                *cell = Cell::new(ch, fg, bg, attrs);
+                written += 1;
+            }
+        }
+        written
+    }
+
+    /// Replaces the foreground and background colors of every cell with the nearest entry of
+    /// `palette`, as found by `Color::nearest`. `Color::Default` is left untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let palette = [Color::Black, Color::Red, Color::Green, Color::Yellow, Color::Blue,
+    ///                Color::Magenta, Color::Cyan, Color::White];
+    /// let mut buf = CellBuffer::new(3, 1, Cell::default());
+    /// buf[(0, 0)] = Cell::new('a', Color::Rgb(190, 20, 10), Color::Rgb(5, 5, 5), Attr::Default);
+    /// buf[(1, 0)] = Cell::new('b', Color::Byte(34), Color::Default, Attr::Default);
+    ///
+    /// buf.quantize_to_palette(&palette);
+    /// assert_eq!((buf[(0, 0)].fg(), buf[(0, 0)].bg()), (Color::Red, Color::Black));
+    /// assert!(palette.contains(&buf[(1, 0)].fg()));
+    /// assert_eq!(buf[(1, 0)].bg(), Color::Default);
+    /// assert_eq!(buf[(2, 0)], Cell::default());
+    /// ```
+    pub fn quantize_to_palette(&mut self, palette: &[Color]) {
+        let quantize = |color: Color| {
+            if color == Color::Default {
+                color
+            } else {
+                color.nearest(palette)
+            }
+        };
+        self.touch_all();
+        for cell in self.buf.iter_mut() {
+            let (fg, bg) = (quantize(cell.fg()), quantize(cell.bg()));
+            cell.set_fg(fg);
+            cell.set_bg(bg);
+        }
+    }
+
+    /// Draws a placeholder for a panel with no content yet: a dashed border around the edge of
+    /// the buffer with `hint` centered inside it, both in `fg`.
+    ///
+    /// Nothing is drawn unless every cell is `Cell::default()`, or if the buffer is smaller than
+    /// 2×2. The hint is truncated to fit inside the border, and left out if the buffer has no
+    /// rows between the top and bottom borders.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color};
+    ///
+    /// let mut buf = CellBuffer::new(9, 3, Cell::default());
+    /// buf.draw_placeholder("empty", Color::Byte(8));
+    ///
+    /// let lines: Vec<String> = buf.char_grid().iter().map(|row| row.iter().collect()).collect();
+    /// assert_eq!(lines, ["┌┄┄┄┄┄┄┄┐",
+    ///                    "┆ empty ┆",
+    ///                    "└┄┄┄┄┄┄┄┘"]);
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Byte(8));
+    ///
+    /// let mut buf = CellBuffer::new(9, 3, Cell::default());
+    /// buf[(4, 1)].set_ch('x');
+    /// let before = buf.clone();
+    /// buf.draw_placeholder("empty", Color::Byte(8));
+    /// assert_eq!(buf, before);
+    ///
+    /// let mut buf = CellBuffer::new(9, 2, Cell::default());
+    /// buf.draw_placeholder("empty", Color::Byte(8));
+    /// let lines: Vec<String> = buf.char_grid().iter().map(|row| row.iter().collect()).collect();
+    /// assert_eq!(lines, ["┌┄┄┄┄┄┄┄┐",
+    ///                    "└┄┄┄┄┄┄┄┘"]);
+    /// ```
+    pub fn draw_placeholder(&mut self, hint: &str, fg: Color) {
+        let (cols, rows) = (self.cols, self.rows);
+        if cols < 2 || rows < 2 || (0..rows).any(|y| !self.row_is_blank(y)) {
+            return;
+        }
+        let cell = Cell::new(' ', fg, Color::Default, Attr::Default);
+        self.draw_border(0, 0, cols, rows, BorderStyle::Dashed, cell);
+        if rows >= 3 {
+            let hint: Vec<char> = hint.chars().take(cols - 2).collect();
+            self.write_centered_line(0, rows / 2, cols, &hint, cell);
+        }
+    }
+
+    /// Returns an iterator over the maximal runs of cells sharing the same style in each row, as
+    /// `(x, y, len, fg, bg, attrs)`, in row-major order.
+    ///
+    /// Runs never span more than one row. See also `row_runs`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(5, 2, Cell::default());
+    /// for y in 0..2 {
+    ///     for x in 3..5 {
+    ///         buf[(x, y)] = Cell::new('r', Color::Red, Color::Default, Attr::Bold);
+    ///     }
+    /// }
+    ///
+    /// let runs: Vec<_> = buf.style_runs().collect();
+    /// assert_eq!(runs, [(0, 0, 3, Color::Default, Color::Default, Attr::Default),
+    ///                   (3, 0, 2, Color::Red, Color::Default, Attr::Bold),
+    ///                   (0, 1, 3, Color::Default, Color::Default, Attr::Default),
+    ///                   (3, 1, 2, Color::Red, Color::Default, Attr::Bold)]);
+    /// ```
+    pub fn style_runs(&self)
+                      -> impl Iterator<Item = (usize, usize, usize, Color, Color, Attr)> + '_ {
+        (0..self.rows).flat_map(move |y| {
+            self.row_runs(y).into_iter().map(move |(x, cells)| {
+                let first = cells[0];
+                (x, y, cells.len(), first.fg(), first.bg(), first.attrs())
+            })
+        })
+    }
+
+    /// Returns a reference to the `Cell` at `(x, y)` without checking that the coordinates are in
+    /// bounds.
+    ///
+    /// The coordinates are only checked with `debug_assert!`, so debug builds still panic on a
+    /// violation.
+    ///
+    /// # Safety
+    ///
+    /// `x` must be less than the number of columns and `y` less than the number of rows.
+    /// Otherwise the behavior is undefined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, CellAccessor};
+    ///
+    /// let mut buf = CellBuffer::new(7, 5, Cell::default());
+    /// for (i, cell) in buf.iter_mut().enumerate() {
+    ///     cell.set_ch((b'a' + (i % 26) as u8) as char);
+    /// }
+    ///
+    /// for y in 0..5 {
+    ///     for x in 0..7 {
+    ///         assert_eq!(unsafe { buf.get_unchecked(x, y) }, buf.get(x, y).unwrap());
+    ///     }
+    /// }
+    ///
+    /// unsafe { buf.get_unchecked_mut(6, 4) }.set_ch('!');
+    /// assert_eq!(buf[(6, 4)].ch(), '!');
+    /// ```
+    pub unsafe fn get_unchecked(&self, x: usize, y: usize) -> &Cell {
+        debug_assert!(x < self.cols && y < self.rows, "index out of bounds");
+        self.buf.get_unchecked(y * self.cols + x)
+    }
+
+    /// Returns a mutable reference to the `Cell` at `(x, y)` without checking that the
+    /// coordinates are in bounds.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as for `get_unchecked` apply.
+    pub unsafe fn get_unchecked_mut(&mut self, x: usize, y: usize) -> &mut Cell {
+        debug_assert!(x < self.cols && y < self.rows, "index out of bounds");
+        let i = y * self.cols + x;
+        self.versions.touch(i);
+        self.buf.get_unchecked_mut(i)
+    }
+
+    /// Returns a copy of the buffer enlarged `factor` times in each direction, with every cell
+    /// repeated into a `factor`×`factor` block. A `factor` of zero returns an empty buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, HasSize};
+    ///
+    /// let mut buf = CellBuffer::new(2, 2, Cell::default());
+    /// for (cell, ch) in buf.iter_mut().zip("abcd".chars()) {
+    ///     cell.set_ch(ch);
+    /// }
+    ///
+    /// let big = buf.scale_nearest(2);
+    /// assert_eq!(big.size(), (4, 4));
+    /// assert_eq!(big.iter().map(|c| c.ch()).collect::<String>(), "aabbaabbccddccdd");
+    /// assert_eq!(buf.scale_nearest(0).size(), (0, 0));
+    /// ```
+    pub fn scale_nearest(&self, factor: usize) -> CellBuffer {
+        let (cols, rows) = (self.cols * factor, self.rows * factor);
+        let mut buf = Vec::with_capacity(cols * rows);
+        for y in 0..rows {
+            for x in 0..cols {
+                buf.push(self.buf[y / factor * self.cols + x / factor]);
+            }
+        }
+        CellBuffer::from_vec(cols, rows, buf)
+    }
+
+    /// Returns an iterator over the cells of column `x` from top to bottom, or `None` if `x` is
+    /// out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, CellAccessor};
+    ///
+    /// let mut buf = CellBuffer::new(3, 4, Cell::default());
+    /// for (i, cell) in buf.iter_mut().enumerate() {
+    ///     cell.set_ch((b'a' + i as u8) as char);
+    /// }
+    ///
+    /// let column: Vec<&Cell> = buf.col_iter(1).unwrap().collect();
+    /// let expected: Vec<&Cell> = (0..4).map(|y| buf.get(1, y).unwrap()).collect();
+    /// assert_eq!(column, expected);
+    /// assert!(buf.col_iter(3).is_none());
+    ///
+    /// for cell in buf.col_iter_mut(2).unwrap() {
+    ///     cell.set_ch('|');
+    /// }
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "ab|de|gh|jk|");
+    /// ```
+    pub fn col_iter(&self, x: usize) -> Option<impl Iterator<Item = &Cell>> {
+        if x < self.cols {
+            Some(self.buf.iter().skip(x).step_by(self.cols))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over mutable references to the cells of column `x` from top to
+    /// bottom, or `None` if `x` is out of bounds.
+    pub fn col_iter_mut(&mut self, x: usize) -> Option<impl Iterator<Item = &mut Cell>> {
+        if x < self.cols {
+            self.touch_all();
+            Some(self.buf.iter_mut().skip(x).step_by(self.cols))
+        } else {
+            None
+        }
+    }
+
+    /// Fills the `width`-column field starting at `(x, y)` with `left` flush against its left
+    /// edge and `right` flush against its right edge, padding the space between with blanks, all
+    /// in the given style.
+    ///
+    /// If the two don't fit together, `left` is kept (truncated to `width` if necessary) and
+    /// `right` is truncated to the space left over. Parts of the field outside the buffer are
+    /// clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let (fg, bg, attrs) = (Color::Default, Color::Default, Attr::Reverse);
+    /// let mut buf = CellBuffer::new(14, 1, Cell::with_char('.'));
+    /// buf.write_justified(1, 0, 12, "main.rs", "12:30", fg, bg, attrs);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), ".main.rs12:30.");
+    ///
+    /// buf.write_justified(1, 0, 12, "lib", "12:30", fg, bg, attrs);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), ".lib    12:30.");
+    /// assert!(buf.iter().skip(1).take(12).all(|c| c.attrs() == Attr::Reverse));
+    ///
+    /// buf.write_justified(1, 0, 12, "cellbuffer.rs", "12:30", fg, bg, attrs);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), ".cellbuffer.r.");
+    ///
+    /// buf.write_justified(1, 0, 12, "cellbuffer", "12:30", fg, bg, attrs);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), ".cellbuffer12.");
+    ///
+    /// buf.write_justified(10, 0, usize::MAX, "ab", "cd", fg, bg, attrs);
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), ".cellbuffeab  ");
+    /// buf.write_justified(usize::MAX, 0, 2, "xy", "", fg, bg, attrs);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_justified(&mut self,
+                           x: usize,
+                           y: usize,
+                           width: usize,
+                           left: &str,
+                           right: &str,
+                           fg: Color,
+                           bg: Color,
+                           attrs: Attr) {
+        let left: Vec<char> = left.chars().take(width).collect();
+        let right: Vec<char> = right.chars().take(width - left.len()).collect();
+        let right_start = width - right.len();
+        let (_, _, visible, _) = self.clamp_rect(x, y, width, 1);
+        for i in 0..visible {
+            let ch = if i < left.len() {
+                left[i]
+            } else if i >= right_start {
+                right[i - right_start]
+            } else {
+                ' '
+            };
+            self[(x + i, y)] = Cell::new(ch, fg, bg, attrs);
+        }
+    }
+
+    /// Darkens the buffer towards its edges, scaling the colors of each cell by a factor that
+    /// falls from 1.0 at the center to `1.0 - strength` at the corners, in proportion to the
+    /// cell's distance from the center.
+    ///
+    /// Scaled colors are stored back as `Color::Rgb`; `Color::Default` and the exact center are
+    /// left untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let white = Color::Rgb(200, 200, 200);
+    /// let mut buf = CellBuffer::new(5, 5, Cell::new('x', white, Color::Default, Attr::Default));
+    /// buf.vignette(0.5);
+    ///
+    /// assert_eq!(buf[(2, 2)].fg(), white);
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Rgb(100, 100, 100));
+    /// assert_eq!(buf[(4, 4)].fg(), Color::Rgb(100, 100, 100));
+    /// assert_eq!(buf[(0, 0)].bg(), Color::Default);
+    /// ```
+    pub fn vignette(&mut self, strength: f32) {
+        let (cx, cy) = ((self.cols as f32 - 1.0) / 2.0, (self.rows as f32 - 1.0) / 2.0);
+        let normalize = |v: usize, center: f32| {
+            if center > 0.0 { (v as f32 - center) / center } else { 0.0 }
+        };
+        self.touch_all();
+        let cols = self.cols;
+        for (i, cell) in self.buf.iter_mut().enumerate() {
+            let (dx, dy) = (normalize(i % cols, cx), normalize(i / cols, cy));
+            let distance = ((dx * dx + dy * dy) / 2.0).sqrt();
+            if distance == 0.0 {
+                continue;
+            }
+            let factor = 1.0 - strength * distance;
+            let (fg, bg) = (cell.fg().scale(factor), cell.bg().scale(factor));
+            cell.set_fg(fg);
+            cell.set_bg(bg);
+        }
+    }
+
+    /// Returns the characters of the buffer as text, one line per row, with trailing spaces
+    /// trimmed from each line and trailing empty lines dropped.
+    ///
+    /// Blank lines between others are kept, and there is no final newline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(8, 5, Cell::default());
+    /// buf.write_segments(0, 0, &[("a  b", Color::Default, Color::Default, Attr::Default)]);
+    /// buf.write_segments(2, 2, &[("c", Color::Default, Color::Default, Attr::Default)]);
+    ///
+    /// assert_eq!(buf.to_plaintext(), "a  b\n\n  c");
+    /// assert_eq!(CellBuffer::new(3, 3, Cell::default()).to_plaintext(), "");
+    /// ```
+    pub fn to_plaintext(&self) -> String {
+        let mut lines: Vec<String> = self.char_grid()
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end_matches(' ').to_string())
+            .collect();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+
+    /// Replaces every cell whose character doesn't satisfy `pred` with `blank`, leaving the
+    /// matching cells untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(6, 1, Cell::default());
+    /// buf.write_segments(0, 0, &[("a1-b2c", Color::Red, Color::Default, Attr::Default)]);
+    ///
+    /// buf.keep_chars(|ch| ch.is_alphabetic(), Cell::with_char('.'));
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "a..b.c");
+    /// assert_eq!(buf[(0, 0)].fg(), Color::Red);
+    /// assert_eq!(buf[(1, 0)].fg(), Color::Default);
+    /// ```
+    pub fn keep_chars<F: Fn(char) -> bool>(&mut self, pred: F, blank: Cell) {
+        self.touch_all();
+        for cell in self.buf.iter_mut() {
+            if !pred(cell.ch()) {
+                *cell = blank;
+            }
+        }
+    }
+
+    /// Resizes the buffer to `size`, given as `(cols, rows)`, using the default `Cell` as a blank.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, HasSize};
+    ///
+    /// let mut buf = CellBuffer::new(4, 2, Cell::with_char('x'));
+    /// buf.resize_to((10, 5));
+    /// assert_eq!(buf.size(), (10, 5));
+    /// assert_eq!(buf[(3, 1)].ch(), 'x');
+    /// assert_eq!(buf[(9, 4)], Cell::default());
+    /// ```
+    pub fn resize_to(&mut self, size: Size) {
+        let (cols, rows) = size;
+        self.resize(cols, rows, Cell::default());
+    }
+
+    /// Like `resize_to`, but takes the size as `u16`s, as reported by the terminal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, HasSize};
+    ///
+    /// let mut buf = CellBuffer::new(4, 2, Cell::default());
+    /// let size: (u16, u16) = (80, 24);
+    /// buf.resize_to_u16(size);
+    /// assert_eq!(buf.size(), (80, 24));
+    /// ```
+    pub fn resize_to_u16(&mut self, size: (u16, u16)) {
+        let (cols, rows) = size;
+        self.resize_to((cols as usize, rows as usize));
+    }
+
+    /// Returns the number of times the style changes between horizontally adjacent cells,
+    /// counting each row separately. Each change costs an SGR sequence when the buffer is drawn.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color};
+    ///
+    /// let mut buf = CellBuffer::new(6, 2, Cell::with_char('x'));
+    /// assert_eq!(buf.style_transitions(), 0);
+    ///
+    /// for x in 0..6 {
+    ///     buf[(x, 0)].set_fg(if x % 2 == 0 { Color::Red } else { Color::Blue });
+    /// }
+    /// assert_eq!(buf.style_transitions(), 5);
+    /// ```
+    pub fn style_transitions(&self) -> usize {
+        if self.cols == 0 {
+            return 0;
+        }
+        self.buf
+            .chunks(self.cols)
+            .map(|row| row.windows(2).filter(|pair| !pair[0].same_style(&pair[1])).count())
+            .sum()
+    }
+
+    /// Dims the drop shadow of the `w`×`h` rectangle with its top-left corner at `(x, y)`, by
+    /// scaling the colors of the cells it covers by `factor`.
+    ///
+    /// The shadow is the rectangle shifted `offset` cells right and down, minus the rectangle
+    /// itself: an L-shaped strip `offset` cells wide along its right and bottom edges. Scaled
+    /// colors are stored back as `Color::Rgb`, `Color::Default` is left untouched, and parts of
+    /// the shadow outside the buffer are clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let white = Color::Rgb(200, 200, 200);
+    /// let mut buf = CellBuffer::new(6, 5, Cell::new(' ', white, white, Attr::Default));
+    /// buf.draw_shadow(1, 1, 3, 2, 1, 0.5);
+    ///
+    /// let shaded: String = buf.iter().map(|c| if c.bg() == white { '.' } else { '#' }).collect();
+    /// assert_eq!(shaded, "......\
+    ///                     ......\
+    ///                     ....#.\
+    ///                     ..###.\
+    ///                     ......");
+    /// assert_eq!(buf[(4, 3)].bg(), Color::Rgb(100, 100, 100));
+    ///
+    /// // A rectangle running off the buffer only casts its visible bottom edge.
+    /// buf.draw_shadow(1, 1, usize::MAX, 2, 1, 0.5);
+    /// assert_eq!(buf[(5, 3)].bg(), Color::Rgb(100, 100, 100));
+    /// assert_eq!(buf[(5, 2)].bg(), white);
+    /// ```
+    pub fn draw_shadow(&mut self,
+                       x: usize,
+                       y: usize,
+                       w: usize,
+                       h: usize,
+                       offset: usize,
+                       factor: f32) {
+        let (right, bottom) = (x.saturating_add(w), y.saturating_add(h));
+        let (sx, sy, sw, sh) =
+            self.clamp_rect(x.saturating_add(offset), y.saturating_add(offset), w, h);
+        for iy in sy..sy + sh {
+            for ix in sx..sx + sw {
+                if ix < right && iy < bottom {
+                    continue;
+                }
+                let cell = &mut self[(ix, iy)];
+                let (fg, bg) = (cell.fg().scale(factor), cell.bg().scale(factor));
+                cell.set_fg(fg);
+                cell.set_bg(bg);
+            }
+        }
+    }
+
+    /// Returns the cell nearest to the fractional coordinates `(fx, fy)`, or `None` if they round
+    /// to a position outside the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(4, 4, Cell::default());
+    /// buf[(1, 3)].set_ch('x');
+    ///
+    /// assert_eq!(buf.sample(1.4, 2.6).map(|c| c.ch()), Some('x'));
+    /// assert_eq!(buf.sample(-0.4, 0.0), Some(&buf[(0, 0)]));
+    /// assert_eq!(buf.sample(-1.0, 0.0), None);
+    /// assert_eq!(buf.sample(0.0, 3.5), None);
+    /// ```
+    pub fn sample(&self, fx: f32, fy: f32) -> Option<&Cell> {
+        let (x, y) = (fx.round(), fy.round());
+        if x >= 0.0 && y >= 0.0 {
+            self.get(x as usize, y as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Draws a `w`×`h` box with its top-left corner at `(x, y)`, with `title` centered in its top
+    /// border and `body` word-wrapped and centered inside it.
+    ///
+    /// The interior of the box is cleared first. The title is truncated to fit between the
+    /// corners, body lines that don't fit inside the box are dropped, and parts of the box outside
+    /// the buffer are clipped. Nothing is drawn if the box is smaller than 2×2.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr, BorderStyle};
+    ///
+    /// let mut buf = CellBuffer::new(12, 5, Cell::default());
+    /// buf.draw_message_box(0, 0, 12, 5, "Error", "file not found", BorderStyle::Single,
+    ///                      Color::Red, Color::Default);
+    ///
+    /// let lines: Vec<String> = buf.char_grid().iter().map(|row| row.iter().collect()).collect();
+    /// assert_eq!(lines, ["┌──Error───┐",
+    ///                    "│ file not │",
+    ///                    "│  found   │",
+    ///                    "│          │",
+    ///                    "└──────────┘"]);
+    /// assert_eq!(buf[(3, 0)].fg(), Color::Red);
+    ///
+    /// buf.draw_message_box(8, 3, usize::MAX, usize::MAX, "Note", "hi", BorderStyle::Double,
+    ///                      Color::Blue, Color::Default);
+    /// assert_eq!(buf[(8, 3)].ch(), '╔');
+    /// assert_eq!(buf[(9, 3)].ch(), '═');
+    /// assert_eq!(buf[(11, 4)], Cell::with_style(Color::Blue, Color::Default, Attr::Default));
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_message_box(&mut self,
+                            x: usize,
+                            y: usize,
+                            w: usize,
+                            h: usize,
+                            title: &str,
+                            body: &str,
+                            style: BorderStyle,
+                            fg: Color,
+                            bg: Color) {
+        if w < 2 || h < 2 {
+            return;
+        }
+        let blank = Cell::new(' ', fg, bg, Attr::Default);
+        let (inner_w, inner_h) = (w - 2, h - 2);
+        self.draw_border(x, y, w, h, style, blank);
+        let (inner_x, inner_y) = (x.saturating_add(1), y.saturating_add(1));
+        let (_, _, visible_w, visible_h) = self.clamp_rect(inner_x, inner_y, inner_w, inner_h);
+        for iy in inner_y..inner_y + visible_h {
+            for ix in inner_x..inner_x + visible_w {
+                self[(ix, iy)] = blank;
+            }
+        }
+
+        let title: Vec<char> = title.chars().take(inner_w).collect();
+        self.write_centered_line(inner_x, y, inner_w, &title, blank);
+        let lines = wrap_text(body, inner_w);
+        let lines = &lines[..lines.len().min(inner_h)];
+        let top = inner_y.saturating_add((inner_h - lines.len()) / 2);
+        for (i, line) in lines.iter().enumerate() {
+            let line: Vec<char> = line.chars().collect();
+            self.write_centered_line(inner_x, top.saturating_add(i), inner_w, &line, blank);
+        }
+    }
+
+    /// Draws the outline of the `w`×`h` rectangle with its top-left corner at `(x, y)` in the
+    /// given `style`, using the colors and attributes of `cell`. Parts outside the buffer are
+    /// clipped, and nothing is drawn if the rectangle is smaller than 2×2.
+    fn draw_border(&mut self,
+                   x: usize,
+                   y: usize,
+                   w: usize,
+                   h: usize,
+                   style: BorderStyle,
+                   cell: Cell) {
+        if w < 2 || h < 2 {
+            return;
+        }
+        let (horizontal, vertical, junctions) = style.glyphs();
+        let (_, _, visible_w, visible_h) = self.clamp_rect(x, y, w, h);
+        for iy in 0..visible_h {
+            for ix in 0..visible_w {
+                let ch = match (ix == 0 || ix == w - 1, iy == 0 || iy == h - 1) {
+                    (true, true) => junctions[iy.min(1) * 2][ix.min(1) * 2],
+                    (false, true) => horizontal,
+                    (true, false) => vertical,
+                    (false, false) => continue,
+                };
+                let target = &mut self[(x + ix, y + iy)];
+                *target = cell;
+                target.set_ch(ch);
+            }
+        }
+    }
+
+    /// Writes `chars`, which must be no longer than `width`, centered within the `width` columns
+    /// starting at `(x, y)` in the style of `cell`, clipping at the buffer edge.
+    fn write_centered_line(&mut self,
+                           x: usize,
+                           y: usize,
+                           width: usize,
+                           chars: &[char],
+                           cell: Cell) {
+        let start = x.saturating_add((width - chars.len()) / 2);
+        for (i, &ch) in chars.iter().enumerate() {
+            if let Some(target) = self.get_mut(start.saturating_add(i), y) {
+                *target = cell;
+                target.set_ch(ch);
+            }
+        }
+    }
+
+    /// Returns the number of cells that differ between `self` and `other` in each row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are not the same size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let old = CellBuffer::new(4, 3, Cell::default());
+    /// let mut new = old.clone();
+    /// new[(0, 0)].set_ch('a');
+    /// new[(1, 2)].set_ch('b');
+    /// new[(3, 2)].set_ch('c');
+    ///
+    /// let counts = new.diff_per_row(&old);
+    /// assert_eq!(counts, [1, 0, 2]);
+    /// assert_eq!(counts.iter().sum::<usize>(), new.diff_iter(&old).count());
+    /// ```
+    pub fn diff_per_row(&self, other: &CellBuffer) -> Vec<usize> {
+        let mut counts = vec![0; self.rows];
+        for (_, y, _) in self.diff_iter(other) {
+            counts[y] += 1;
+        }
+        counts
+    }
+
+    /// Sets the background of every cell in even rows to `even_bg` and in odd rows to `odd_bg`,
+    /// leaving characters, foregrounds and attributes untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let mut buf = CellBuffer::new(3, 3, Cell::new('x', Color::Red, Color::Default,
+    ///                                             Attr::Default));
+    /// buf.zebra_stripe(Color::Black, Color::Byte(236));
+    ///
+    /// assert!((0..3).all(|x| buf[(x, 0)].bg() == Color::Black));
+    /// assert!((0..3).all(|x| buf[(x, 1)].bg() == Color::Byte(236)));
+    /// assert!((0..3).all(|x| buf[(x, 2)].bg() == Color::Black));
+    /// assert!(buf.iter().all(|c| c.ch() == 'x' && c.fg() == Color::Red));
+    /// ```
+    pub fn zebra_stripe(&mut self, even_bg: Color, odd_bg: Color) {
+        self.touch_all();
+        let cols = self.cols;
+        for (i, cell) in self.buf.iter_mut().enumerate() {
+            cell.set_bg(if (i / cols).is_multiple_of(2) { even_bg } else { odd_bg });
+        }
+    }
+
+    /// Returns a copy of the buffer with the given number of columns and rows, keeping the
+    /// contents anchored at the top-left corner and filling any new cells with `blank`.
+    ///
+    /// Unlike `resize`, `self` is left untouched. The copy starts without checkpoints or version
+    /// history.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, HasSize};
+    ///
+    /// let mut buf = CellBuffer::new(2, 2, Cell::default());
+    /// for (cell, ch) in buf.iter_mut().zip("abcd".chars()) {
+    ///     cell.set_ch(ch);
+    /// }
+    /// let original = buf.clone();
+    ///
+    /// let larger = buf.clone_resized(3, 3, Cell::with_char('.'));
+    /// assert_eq!(larger.iter().map(|c| c.ch()).collect::<String>(), "ab.cd....");
+    ///
+    /// let smaller = buf.clone_resized(1, 2, Cell::default());
+    /// assert_eq!(smaller.iter().map(|c| c.ch()).collect::<String>(), "ac");
+    ///
+    /// assert_eq!(buf, original);
+    /// assert_eq!(buf.size(), (2, 2));
+    /// ```
+    pub fn clone_resized(&self, newcols: usize, newrows: usize, blank: Cell) -> CellBuffer {
+        let mut buf = Vec::with_capacity(newcols * newrows);
+        for y in 0..newrows {
+            for x in 0..newcols {
+                buf.push(*self.get(x, y).unwrap_or(&blank));
+            }
+        }
+        CellBuffer::from_vec(newcols, newrows, buf)
+    }
+
+    /// Shifts every row one column to the left, wrapping the leftmost cell around to the
+    /// rightmost column.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let mut buf = CellBuffer::new(4, 2, Cell::default());
+    /// for (cell, ch) in buf.iter_mut().zip("abcdefgh".chars()) {
+    ///     cell.set_ch(ch);
+    /// }
+    /// let original = buf.clone();
+    ///
+    /// buf.roll_left();
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "bcdafghe");
+    /// buf.roll_right();
+    /// assert_eq!(buf, original);
+    /// buf.roll_right();
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), "dabchefg");
+    /// ```
+    pub fn roll_left(&mut self) {
+        if self.cols == 0 {
+            return;
+        }
+        self.touch_all();
+        for row in self.buf.chunks_mut(self.cols) {
+            row.rotate_left(1);
+        }
+    }
+
+    /// Shifts every row one column to the right, wrapping the rightmost cell around to the
+    /// leftmost column.
+    pub fn roll_right(&mut self) {
+        if self.cols == 0 {
+            return;
+        }
+        self.touch_all();
+        for row in self.buf.chunks_mut(self.cols) {
+            row.rotate_right(1);
+        }
+    }
+
+    /// Returns the smallest rectangle, as `(x, y, w, h)`, enclosing every cell that differs
+    /// between `self` and `other`, or `None` if the buffers are identical.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers are not the same size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell};
+    ///
+    /// let old = CellBuffer::new(8, 6, Cell::default());
+    /// let mut new = old.clone();
+    /// assert_eq!(new.diff_bbox(&old), None);
+    ///
+    /// new[(1, 4)].set_ch('a');
+    /// new[(6, 1)].set_ch('b');
+    /// assert_eq!(new.diff_bbox(&old), Some((1, 1, 6, 4)));
+    /// ```
+    pub fn diff_bbox(&self, other: &CellBuffer) -> Option<(usize, usize, usize, usize)> {
+        self.diff_iter(other)
+            .fold(None, |bounds: Option<(usize, usize, usize, usize)>, (x, y, _)| {
+                Some(match bounds {
+                    None => (x, y, x, y),
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                })
+            })
+            .map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+
+    /// Writes `s` starting at `(x, y)` using the given style, and returns the position just
+    /// after the last character written so the next write can continue from there.
+    ///
+    /// Text reaching the right edge of the buffer wraps to the start of the next row, as does
+    /// each `\n`; other control characters are skipped. Writing stops at the bottom of the
+    /// buffer, in which case the returned row is one past the last.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{CellBuffer, Cell, Color, Attr};
+    ///
+    /// let (fg, bg, attrs) = (Color::Default, Color::Default, Attr::Default);
+    /// let mut buf = CellBuffer::new(5, 3, Cell::with_char('.'));
+    ///
+    /// let (x, y) = buf.write_str_at(1, 0, "ab", fg, bg, attrs);
+    /// assert_eq!((x, y), (3, 0));
+    /// let (x, y) = buf.write_str_at(x, y, "cdef", fg, bg, attrs);
+    /// assert_eq!((x, y), (2, 1));
+    /// assert_eq!(buf.iter().map(|c| c.ch()).collect::<String>(), ".abcdef........");
+    ///
+    /// assert_eq!(buf.write_str_at(0, 2, "ghijk", fg, bg, attrs), (0, 3));
+    /// ```
+    pub fn write_str_at(&mut self,
+                        x: usize,
+                        y: usize,
+                        s: &str,
+                        fg: Color,
+                        bg: Color,
+                        attrs: Attr)
+                        -> Pos {
+        let (mut x, mut y) = (x, y);
+        for ch in s.chars() {
+            if y >= self.rows {
+                break;
+            }
+            if ch == '\n' {
+                x = 0;
+                y += 1;
+                continue;
+            }
+            if ch.is_control() {
+                continue;
+            }
+            if x >= self.cols {
+                x = 0;
+                y += 1;
+                if y >= self.rows {
+                    break;
+                }
+            }
+            self[(x, y)] = Cell::new(ch, fg, bg, attrs);
+            x += 1;
+        }
+        if x >= self.cols && y < self.rows {
+            x = 0;
+            y += 1;
+        }
+        (x, y)
+    }
+
+    /// Sets the foreground and background colors of every cell whose character is a key of
+    /// `map` to the `(fg, bg)` pair it maps to. Cells with other characters are left untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rustty::{CellBuffer, Color, Attr};
+    ///
+    /// let maze = vec!["#.#".chars().collect(), "#.@".chars().collect()];
+    /// let mut buf = CellBuffer::from_char_grid(&maze, Color::Red, Color::Red, Attr::Default);
+    ///
+    /// let mut colors = HashMap::new();
+    /// colors.insert('#', (Color::White, Color::Black));
+    /// colors.insert('.', (Color::Black, Color::White));
+    /// buf.apply_char_colormap(&colors);
+    ///
+    /// assert_eq!((buf[(0, 0)].fg(), buf[(0, 0)].bg()), (Color::White, Color::Black));
+    /// assert_eq!((buf[(1, 1)].fg(), buf[(1, 1)].bg()), (Color::Black, Color::White));
+    /// assert_eq!((buf[(2, 1)].fg(), buf[(2, 1)].bg()), (Color::Red, Color::Red));
+    /// ```
+    pub fn apply_char_colormap(&mut self, map: &HashMap<char, (Color, Color)>) {
+        self.touch_all();
+        for cell in self.buf.iter_mut() {
+            if let Some(&(fg, bg)) = map.get(&cell.ch()) {
+                cell.set_fg(fg);
+                cell.set_bg(bg);
+            }
+        }
+    }
+}
+
+// Lower block glyphs, indexed by the number of eighths of the cell they fill.
+const BLOCK_GLYPHS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Word-wraps `text` to `width` columns, returning the resulting rows.
+///
+/// Words longer than `width` are broken across rows and each `\n` forces a break. Returns no rows
+/// if `width` is zero.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    if width == 0 {
+        return rows;
+    }
+    for line in text.lines() {
+        let mut current: Vec<char> = Vec::new();
+        for word in line.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            if !current.is_empty() && current.len() + 1 + word.len() <= width {
+                current.push(' ');
+                current.extend(word);
+                continue;
+            }
+            if !current.is_empty() {
+                rows.push(current.drain(..).collect());
+            }
+            while word.len() > width {
+                rows.push(word.drain(..width).collect());
+            }
+            current = word;
+        }
+        rows.push(current.into_iter().collect());
+    }
+    rows
+}
+
+/// Averages the given colors in RGB, ignoring `Color::Default`.
+///
+/// If every non-default color is identical it is returned unchanged, and if there are no
+/// non-default colors the result is `Color::Default`.
+fn average_color<I: Iterator<Item = Color>>(colors: I) -> Color {
+    let mut first = None;
+    let mut uniform = true;
+    let (mut r, mut g, mut b, mut n) = (0u32, 0u32, 0u32, 0u32);
+    for color in colors {
+        if let Some((cr, cg, cb)) = color.to_rgb() {
+            match first {
+                None => first = Some(color),
+                Some(f) if f != color => uniform = false,
+                _ => {}
+            }
+            r += cr as u32;
+            g += cg as u32;
+            b += cb as u32;
+            n += 1;
+        }
+    }
+    match first {
+        None => Color::Default,
+        Some(f) if uniform => f,
+        _ => Color::Rgb((r / n) as u8, (g / n) as u8, (b / n) as u8),
+    }
+}
+
+/// Converts an RGB triple to `(hue, saturation, lightness)`, with hue in degrees.
+fn rgb_to_hsl((r, g, b): (u8, u8, u8)) -> (f32, f32, f32) {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0.0, 0.0, l);
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * (((g - b) / d) % 6.0)
+    } else if max == g {
+        60.0 * ((b - r) / d + 2.0)
+    } else {
+        60.0 * ((r - g) / d + 4.0)
+    };
+    ((h + 360.0) % 360.0, s, l)
+}
+
+/// Converts `(hue, saturation, lightness)`, with hue in degrees, to an RGB triple.
+fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (u8, u8, u8) {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = l - c / 2.0;
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let channel = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+impl HasSize for CellBuffer {
+    fn size(&self) -> Size {
+        (self.cols, self.rows)
+    }
+}
+
+impl CellAccessor for CellBuffer {
+    fn cellvec(&self) -> &Vec<Cell> {
+        &self.buf
+    }
+
+    fn cellvec_mut(&mut self) -> &mut Vec<Cell> {
+        self.touch_all();
+        &mut self.buf
+    }
+
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Cell> {
+        match self.pos_to_index(x, y) {
+            Some(i) => {
+                self.versions.touch(i);
+                self.buf.get_mut(i)
+            }
+            None => None,
+        }
+    }
+}
+
+impl Deref for CellBuffer {
+    type Target = [Cell];
+
+    fn deref<'a>(&'a self) -> &'a [Cell] {
+        &self.buf
+    }
+}
+
+impl DerefMut for CellBuffer {
+    fn deref_mut<'a>(&'a mut self) -> &'a mut [Cell] {
+        self.touch_all();
+        &mut self.buf
+    }
+}
+
+impl Index<Pos> for CellBuffer {
+    type Output = Cell;
+
+    fn index<'a>(&'a self, index: Pos) -> &'a Cell {
+        let (x, y) = index;
+        self.get(x, y).expect("index out of bounds")
+    }
+}
+
+impl IndexMut<Pos> for CellBuffer {
+    fn index_mut<'a>(&'a mut self, index: Pos) -> &'a mut Cell {
+        let (x, y) = index;
+        self.get_mut(x, y).expect("index out of bounds")
+    }
+}
+
+impl Default for CellBuffer {
+    /// Constructs a new `CellBuffer` with a size of `(0, 0)`, using the default `Cell` as a blank.
+    fn default() -> CellBuffer {
+        CellBuffer::new(0, 0, Cell::default())
+    }
+}
+
+/// A single point on a terminal display.
+///
+/// A `Cell` contains a character and style.
+///
+/// `Cell`s are ordered by character, then by foreground color, background color and attributes.
+///
+/// # Examples
+///
+/// ```
+/// use rustty::{Cell, Color, Attr};
+///
+/// let mut cells = vec![Cell::new('b', Color::Default, Color::Default, Attr::Default),
+///                      Cell::new('a', Color::Default, Color::Default, Attr::Default),
+///                      Cell::new('a', Color::Red, Color::Default, Attr::Bold),
+///                      Cell::new('a', Color::Red, Color::Default, Attr::Default)];
+/// cells.sort();
+///
+/// assert_eq!(cells, [Cell::new('a', Color::Red, Color::Default, Attr::Default),
+///                    Cell::new('a', Color::Red, Color::Default, Attr::Bold),
+///                    Cell::new('a', Color::Default, Color::Default, Attr::Default),
+///                    Cell::new('b', Color::Default, Color::Default, Attr::Default)]);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cell {
+    ch: char,
+    fg: Color,
+    bg: Color,
+    attrs: Attr,
+}
+
+impl Cell {
+    /// Creates a new `Cell` with the given `char`, `Color`s and `Attr`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let cell = Cell::new('x', Color::Default, Color::Green, Attr::Default);
+    /// assert_eq!(cell.ch(), 'x');
+    /// assert_eq!(cell.fg(), Color::Default);
+    /// assert_eq!(cell.bg(), Color::Green);
+    /// assert_eq!(cell.attrs(), Attr::Default);
+    /// ```
+    pub fn new(ch: char, fg: Color, bg: Color, attrs: Attr) -> Cell {
+        Cell {
+            ch: ch,
+            fg: fg,
+            bg: bg,
+            attrs: attrs,
+        }
+    }
+
+    /// Creates a new `Cell` with the given `char` and default style.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let mut cell = Cell::with_char('x');
+    /// assert_eq!(cell.ch(), 'x');
+    /// assert_eq!(cell.fg(), Color::Default);
+    /// assert_eq!(cell.bg(), Color::Default);
+    /// assert_eq!(cell.attrs(), Attr::Default);
+    /// ```
+    pub fn with_char(ch: char) -> Cell {
+        Cell::new(ch, Color::Default, Color::Default, Attr::Default)
+    }
+
+    /// Creates a new `Cell` with the given style and a blank `char`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let mut cell = Cell::with_style(Color::Default, Color::Red, Attr::Bold);
+    /// assert_eq!(cell.fg(), Color::Default);
+    /// assert_eq!(cell.bg(), Color::Red);
+    /// assert_eq!(cell.attrs(), Attr::Bold);
+    /// assert_eq!(cell.ch(), ' ');
+    /// ```
+    pub fn with_style(fg: Color, bg: Color, attr: Attr) -> Cell {
+        Cell::new(' ', fg, bg, attr)
+    }
+
+    /// Returns the `Cell`'s character.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Cell;
+    ///
+    /// let mut cell = Cell::with_char('x');
+    /// assert_eq!(cell.ch(), 'x');
+    /// ```
+    pub fn ch(&self) -> char {
+        self.ch
+    }
+
+    /// Sets the `Cell`'s character to the given `char`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Cell;
+    ///
+    /// let mut cell = Cell::with_char('x');
+    /// assert_eq!(cell.ch(), 'x');
+    ///
+    /// cell.set_ch('y');
+    /// assert_eq!(cell.ch(), 'y');
+    /// ```
+    pub fn set_ch(&mut self, newch: char) -> &mut Cell {
+        self.ch = newch;
+        self
+    }
+
+    /// Returns the `Cell`'s foreground `Color`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let mut cell = Cell::with_style(Color::Blue, Color::Default, Attr::Default);
+    /// assert_eq!(cell.fg(), Color::Blue);
+    /// ```
+    pub fn fg(&self) -> Color {
+        self.fg
+    }
+
+    /// Sets the `Cell`'s foreground `Color` to the given `Color`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let mut cell = Cell::default();
+    /// assert_eq!(cell.fg(), Color::Default);
+    ///
+    /// cell.set_fg(Color::White);
+    /// assert_eq!(cell.fg(), Color::White);
+    /// ```
+    pub fn set_fg(&mut self, newfg: Color) -> &mut Cell {
+        self.fg = newfg;
+        self
+    }
+
+    /// Returns the `Cell`'s background `Color`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let mut cell = Cell::with_style(Color::Default, Color::Green, Attr::Default);
+    /// assert_eq!(cell.bg(), Color::Green);
+    /// ```
+    pub fn bg(&self) -> Color {
+        self.bg
+    }
+
+    /// Sets the `Cell`'s background `Color` to the given `Color`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let mut cell = Cell::default();
+    /// assert_eq!(cell.bg(), Color::Default);
+    ///
+    /// cell.set_bg(Color::Black);
+    /// assert_eq!(cell.bg(), Color::Black);
+    /// ```
+    pub fn set_bg(&mut self, newbg: Color) -> &mut Cell {
+        self.bg = newbg;
+        self
+    }
+
+    pub fn attrs(&self) -> Attr {
+        self.attrs
+    }
+
+    pub fn set_attrs(&mut self, newattrs: Attr) -> &mut Cell {
+        self.attrs = newattrs;
+        self
+    }
+
+    /// Sets the `Cell`'s character, `Color`s and `Attr` at once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let mut cell = Cell::default();
+    /// cell.set_all('x', Color::Red, Color::Blue, Attr::Bold);
+    /// assert_eq!(cell, Cell::new('x', Color::Red, Color::Blue, Attr::Bold));
+    /// ```
+    pub fn set_all(&mut self, ch: char, fg: Color, bg: Color, attrs: Attr) -> &mut Cell {
+        *self = Cell::new(ch, fg, bg, attrs);
+        self
+    }
+
+    /// Returns a copy of the `Cell` with the fields set in `patch` replaced, inheriting the
+    /// remaining fields from `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, CellPatch, Color, Attr};
+    ///
+    /// let base = Cell::new('x', Color::Red, Color::Blue, Attr::Bold);
+    ///
+    /// let patch = CellPatch { ch: Some('y'), ..CellPatch::default() };
+    /// assert_eq!(base.merge(&patch), Cell::new('y', Color::Red, Color::Blue, Attr::Bold));
+    ///
+    /// let patch = CellPatch {
+    ///     ch: Some('z'),
+    ///     fg: Some(Color::Green),
+    ///     bg: Some(Color::Default),
+    ///     attrs: Some(Attr::Underline),
+    /// };
+    /// let merged = base.merge(&patch);
+    /// assert_eq!(merged, Cell::new('z', Color::Green, Color::Default, Attr::Underline));
+    /// ```
+    pub fn merge(&self, patch: &CellPatch) -> Cell {
+        Cell::new(patch.ch.unwrap_or(self.ch),
+                  patch.fg.unwrap_or(self.fg),
+                  patch.bg.unwrap_or(self.bg),
+                  patch.attrs.unwrap_or(self.attrs))
+    }
+
+    /// Returns `true` if `self` and `other` have the same colors and attributes, regardless of
+    /// their characters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let a = Cell::new('a', Color::Red, Color::Default, Attr::Bold);
+    /// let b = Cell::new('b', Color::Red, Color::Default, Attr::Bold);
+    /// assert!(a.same_style(&b));
+    /// assert!(!a.same_style(&Cell::with_char('a')));
+    /// ```
+    pub fn same_style(&self, other: &Cell) -> bool {
+        self.fg == other.fg && self.bg == other.bg && self.attrs == other.attrs
+    }
+
+    /// Returns a copy of the `Cell` with box-drawing and block characters replaced by ASCII
+    /// approximations, for terminals that can't display them.
+    ///
+    /// Other characters and the style are unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Cell;
+    ///
+    /// assert_eq!(Cell::with_char('─').ascii_fallback().ch(), '-');
+    /// assert_eq!(Cell::with_char('┼').ascii_fallback().ch(), '+');
+    /// assert_eq!(Cell::with_char('█').ascii_fallback().ch(), '#');
+    /// assert_eq!(Cell::with_char('x').ascii_fallback().ch(), 'x');
+    /// ```
+    pub fn ascii_fallback(&self) -> Cell {
+        let ch = match self.ch {
+            '─' | '━' | '═' | '┄' | '┈' | '╌' => '-',
+            '│' | '┃' | '║' | '┆' | '┊' | '╎' => '|',
+            '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼' | '╭' | '╮' | '╰' | '╯' |
+            '┏' | '┓' | '┗' | '┛' | '┣' | '┫' | '┳' | '┻' | '╋' | '╔' | '╗' | '╚' | '╝' |
+            '╠' | '╣' | '╦' | '╩' | '╬' => '+',
+            '█' | '▓' | '▀' | '▄' | '▌' | '▐' => '#',
+            '▒' => ':',
+            '░' => '.',
+            '▁' | '▂' | '▃' => '_',
+            '▅' | '▆' | '▇' => '=',
+            ch => ch,
+        };
+        Cell { ch, ..*self }
+    }
+
+    /// Returns the `Cell` with `attr` added to its attributes if `cond` is `true`, or unchanged
+    /// otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color, Attr};
+    ///
+    /// let cell = Cell::new('x', Color::Default, Color::Default, Attr::Bold);
+    /// assert_eq!(cell.with_attr_if(true, Attr::Reverse).attrs(), Attr::BoldReverse);
+    /// assert_eq!(cell.with_attr_if(false, Attr::Reverse).attrs(), Attr::Bold);
+    ///
+    /// let rows: Vec<Cell> = (0..4)
+    ///     .map(|i| Cell::default().with_attr_if(i % 2 == 1, Attr::Reverse))
+    ///     .collect();
+    /// assert_eq!(rows[1].attrs(), Attr::Reverse);
+    /// assert_eq!(rows[2].attrs(), Attr::Default);
+    /// ```
+    pub fn with_attr_if(mut self, cond: bool, attr: Attr) -> Cell {
+        if cond {
+            self.attrs = self.attrs | attr;
+        }
+        self
+    }
+}
+
+impl Default for Cell {
+    /// Constructs a new `Cell` with a blank `char` and default `Color`s.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{Cell, Color};
+    ///
+    /// let mut cell = Cell::default();
+    /// assert_eq!(cell.ch(), ' ');
+    /// assert_eq!(cell.fg(), Color::Default);
+    /// assert_eq!(cell.bg(), Color::Default);
+    /// ```
+    fn default() -> Cell {
+        Cell::new(' ', Color::Default, Color::Default, Attr::Default)
+    }
+}
+
+/// A partial update to a `Cell`.
+///
+/// Fields set to `None` are inherited from the base cell when the patch is applied with
+/// `Cell::merge`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CellPatch {
+    pub ch: Option<char>,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub attrs: Option<Attr>,
+}
 
 /// The color of a `Cell`.
 ///
@@ -361,6 +4594,12 @@ impl Default for Cell {
 ///
 /// `Color::Byte(..)` may be used to specify a color in the 8-bit range.
 ///
+/// `Color::Rgb(..)` may be used to specify a 24-bit color. When written to the terminal it is
+/// approximated by the nearest color in the 8-bit range.
+///
+/// `Color`s are ordered as the variants are declared: the basic colors from `Black` to `White`,
+/// then `Byte`s and `Rgb`s by value, then `Default`.
+///
 /// # Examples
 ///
 /// ```
@@ -378,7 +4617,7 @@ impl Default for Cell {
 /// // Basic colors are also 8-bit colors (but not vice-versa).
 /// assert_eq!(red.as_byte(), fancy.as_byte())
 /// ```
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Color {
     Black,
     Red,
@@ -389,6 +4628,7 @@ pub enum Color {
     Cyan,
     White,
     Byte(u8),
+    Rgb(u8, u8, u8),
     Default,
 }
 
@@ -405,9 +4645,298 @@ impl Color {
             Color::Cyan => 0x06,
             Color::White => 0x07,
             Color::Byte(b) => b,
+            Color::Rgb(r, g, b) => 16 + 36 * cube_index(r) + 6 * cube_index(g) + cube_index(b),
             Color::Default => panic!("Attempted to cast default color to u8"),
         }
     }
+
+    /// Returns the `(r, g, b)` components of the `Color`, or `None` for `Color::Default`.
+    ///
+    /// The basic and 8-bit colors are resolved using the standard xterm palette.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// assert_eq!(Color::Red.to_rgb(), Some((205, 0, 0)));
+    /// assert_eq!(Color::Byte(0xe8).to_rgb(), Some((8, 8, 8)));
+    /// assert_eq!(Color::Rgb(1, 2, 3).to_rgb(), Some((1, 2, 3)));
+    /// assert_eq!(Color::Default.to_rgb(), None);
+    /// ```
+    pub fn to_rgb(&self) -> Option<(u8, u8, u8)> {
+        match *self {
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+            Color::Default => None,
+            c => {
+                let byte = c.as_byte();
+                Some(match byte {
+                    0x00..=0x0f => BASE_PALETTE[byte as usize],
+                    0x10..=0xe7 => {
+                        let i = byte - 0x10;
+                        (CUBE_LEVELS[(i / 36) as usize],
+                         CUBE_LEVELS[((i / 6) % 6) as usize],
+                         CUBE_LEVELS[(i % 6) as usize])
+                    }
+                    _ => {
+                        let v = 8 + 10 * (byte - 0xe8);
+                        (v, v, v)
+                    }
+                })
+            }
+        }
+    }
+
+    /// Returns the Euclidean distance between two colors in RGB space.
+    ///
+    /// `Color::Default` has no RGB value; it is at distance 0 from itself and infinitely far from
+    /// every other color.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// assert_eq!(Color::Red.distance(Color::Red), 0.0);
+    /// assert_eq!(Color::Rgb(0, 0, 0).distance(Color::Rgb(3, 4, 0)), 5.0);
+    /// assert!(Color::Default.distance(Color::Black).is_infinite());
+    /// ```
+    pub fn distance(&self, other: Color) -> f32 {
+        match (self.to_rgb(), other.to_rgb()) {
+            (Some((r1, g1, b1)), Some((r2, g2, b2))) => {
+                let dr = r1 as f32 - r2 as f32;
+                let dg = g1 as f32 - g2 as f32;
+                let db = b1 as f32 - b2 as f32;
+                (dr * dr + dg * dg + db * db).sqrt()
+            }
+            (None, None) => 0.0,
+            _ => f32::INFINITY,
+        }
+    }
+
+    /// Returns the entry of `palette` closest to `self`.
+    ///
+    /// Returns `self` if no entry is at a finite distance, such as when `palette` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// let palette = [Color::Black, Color::Red, Color::Green, Color::Yellow, Color::Blue,
+    ///                Color::Magenta, Color::Cyan, Color::White];
+    /// assert_eq!(Color::Rgb(190, 20, 10).nearest(&palette), Color::Red);
+    /// assert_eq!(Color::Rgb(240, 240, 235).nearest(&palette), Color::White);
+    /// ```
+    pub fn nearest(&self, palette: &[Color]) -> Color {
+        let mut best = *self;
+        let mut best_distance = f32::INFINITY;
+        for &color in palette {
+            let d = self.distance(color);
+            if d < best_distance {
+                best = color;
+                best_distance = d;
+            }
+        }
+        best
+    }
+
+    /// Linearly interpolates between `self` and `other` in RGB, where a `t` of 0.0 yields `self`
+    /// and 1.0 yields `other`.
+    ///
+    /// Blending two distinct colors yields a `Color::Rgb`. `Color::Default` can't be blended, so
+    /// if either color is the default the nearer of the two is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// let black = Color::Rgb(0, 0, 0);
+    /// let white = Color::Rgb(255, 255, 255);
+    /// assert_eq!(black.blend(white, 0.5), Color::Rgb(128, 128, 128));
+    /// assert_eq!(Color::Red.blend(white, 0.0), Color::Red);
+    /// assert_eq!(Color::Default.blend(white, 0.75), white);
+    /// ```
+    pub fn blend(&self, other: Color, t: f32) -> Color {
+        if t <= 0.0 || *self == other {
+            return *self;
+        }
+        if t >= 1.0 {
+            return other;
+        }
+        match (self.to_rgb(), other.to_rgb()) {
+            (Some((r1, g1, b1)), Some((r2, g2, b2))) => {
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+                Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+            }
+            _ if t < 0.5 => *self,
+            _ => other,
+        }
+    }
+
+    /// Multiplies each RGB channel of the color by `factor`, clamping to the valid range.
+    /// `Color::Default` is returned unchanged.
+    fn scale(&self, factor: f32) -> Color {
+        match self.to_rgb() {
+            Some((r, g, b)) => {
+                let channel = |v: u8| (v as f32 * factor).round().clamp(0.0, 255.0) as u8;
+                Color::Rgb(channel(r), channel(g), channel(b))
+            }
+            None => *self,
+        }
+    }
+
+    /// Returns the foreground `Color` set by the SGR code `code`, or `None` if `code` doesn't set
+    /// a foreground color.
+    ///
+    /// Codes 30..37 map to the basic colors, 90..97 to their bright variants (`Color::Byte(8..15)`)
+    /// and 39 to `Color::Default`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// assert_eq!(Color::from_ansi_fg(31), Some(Color::Red));
+    /// assert_eq!(Color::from_ansi_fg(92), Some(Color::Byte(10)));
+    /// assert_eq!(Color::from_ansi_fg(39), Some(Color::Default));
+    /// assert_eq!(Color::from_ansi_fg(999), None);
+    /// ```
+    pub fn from_ansi_fg(code: u16) -> Option<Color> {
+        match code {
+            30..=37 => Some(basic_color((code - 30) as u8)),
+            39 => Some(Color::Default),
+            90..=97 => Some(Color::Byte((code - 90 + 8) as u8)),
+            _ => None,
+        }
+    }
+
+    /// Returns the background `Color` set by the SGR code `code`, or `None` if `code` doesn't set
+    /// a background color.
+    ///
+    /// Codes 40..47 map to the basic colors, 100..107 to their bright variants
+    /// (`Color::Byte(8..15)`) and 49 to `Color::Default`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// assert_eq!(Color::from_ansi_bg(44), Some(Color::Blue));
+    /// assert_eq!(Color::from_ansi_bg(107), Some(Color::Byte(15)));
+    /// assert_eq!(Color::from_ansi_bg(49), Some(Color::Default));
+    /// assert_eq!(Color::from_ansi_bg(31), None);
+    /// ```
+    pub fn from_ansi_bg(code: u16) -> Option<Color> {
+        match code {
+            40..=47 => Some(basic_color((code - 40) as u8)),
+            49 => Some(Color::Default),
+            100..=107 => Some(Color::Byte((code - 100 + 8) as u8)),
+            _ => None,
+        }
+    }
+
+    /// Parses a `Color` from a string.
+    ///
+    /// Accepts the names of the basic colors and `"default"` (case-insensitively), a decimal
+    /// 8-bit color such as `"200"`, or an RGB color in hex such as `"#ff8800"`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// assert_eq!(Color::parse("green"), Ok(Color::Green));
+    /// assert_eq!(Color::parse("Default"), Ok(Color::Default));
+    /// assert_eq!(Color::parse("200"), Ok(Color::Byte(200)));
+    /// assert_eq!(Color::parse("#abcdef"), Ok(Color::Rgb(0xab, 0xcd, 0xef)));
+    /// assert!(Color::parse("chartreuse").is_err());
+    /// assert!(Color::parse("256").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Color, ColorParseError> {
+        let err = || ColorParseError(s.to_string());
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 || !hex.is_ascii() {
+                return Err(err());
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| err());
+            return Ok(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+        }
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return s.parse().map(Color::Byte).map_err(|_| err());
+        }
+        match &*s.to_lowercase() {
+            "black" => Ok(Color::Black),
+            "red" => Ok(Color::Red),
+            "green" => Ok(Color::Green),
+            "yellow" => Ok(Color::Yellow),
+            "blue" => Ok(Color::Blue),
+            "magenta" => Ok(Color::Magenta),
+            "cyan" => Ok(Color::Cyan),
+            "white" => Ok(Color::White),
+            "default" => Ok(Color::Default),
+            _ => Err(err()),
+        }
+    }
+
+    /// Returns `true` if `self` and `other` differ by at most `tolerance` in each RGB channel.
+    ///
+    /// `Color::Default` is only approximately equal to itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Color;
+    ///
+    /// assert!(Color::Rgb(254, 0, 0).approx_eq(Color::Rgb(255, 0, 0), 2));
+    /// assert!(!Color::Rgb(254, 0, 0).approx_eq(Color::Rgb(255, 0, 0), 0));
+    /// assert!(Color::Byte(196).approx_eq(Color::Rgb(255, 0, 0), 0));
+    /// assert!(Color::Default.approx_eq(Color::Default, 0));
+    /// assert!(!Color::Default.approx_eq(Color::Black, 255));
+    /// ```
+    pub fn approx_eq(&self, other: Color, tolerance: u8) -> bool {
+        match (self.to_rgb(), other.to_rgb()) {
+            (Some((r1, g1, b1)), Some((r2, g2, b2))) => {
+                r1.abs_diff(r2) <= tolerance && g1.abs_diff(g2) <= tolerance &&
+                b1.abs_diff(b2) <= tolerance
+            }
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+// The xterm defaults for the sixteen basic colors, 0x00..0x0f.
+const BASE_PALETTE: [(u8, u8, u8); 16] = [(0, 0, 0),
+                                          (205, 0, 0),
+                                          (0, 205, 0),
+                                          (205, 205, 0),
+                                          (0, 0, 238),
+                                          (205, 0, 205),
+                                          (0, 205, 205),
+                                          (229, 229, 229),
+                                          (127, 127, 127),
+                                          (255, 0, 0),
+                                          (0, 255, 0),
+                                          (255, 255, 0),
+                                          (92, 92, 255),
+                                          (255, 0, 255),
+                                          (0, 255, 255),
+                                          (255, 255, 255)];
+
+// Channel intensities of the 6x6x6 color cube, 0x10..0xe7.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Returns the index of the color cube level closest to the channel value `v`.
+fn cube_index(v: u8) -> u8 {
+    if v < 48 {
+        0
+    } else if v < 115 {
+        1
+    } else {
+        (v - 35) / 40
+    }
 }
 
 /// The attributes of a `Cell`.
@@ -416,6 +4945,8 @@ impl Color {
 ///
 /// `Attr::Default` represents no attribute.
 ///
+/// `Attr`s are ordered by their bit representation (see `Attr::bits`).
+///
 /// # Examples
 ///
 /// ```
@@ -430,7 +4961,7 @@ impl Color {
 /// // Combination.
 /// let comb = Attr::UnderlineReverse;
 /// ```
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Attr {
     Default = 0b000,
     Bold = 0b001,
@@ -441,3 +4972,349 @@ pub enum Attr {
     UnderlineReverse = 0b110,
     BoldReverseUnderline = 0b111,
 }
+
+impl Attr {
+    /// Returns the bit representation of the `Attr`: bold is `0b001`, underline `0b010` and
+    /// reverse `0b100`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Attr;
+    ///
+    /// assert_eq!(Attr::Default.bits(), 0b000);
+    /// assert_eq!(Attr::BoldReverse.bits(), 0b101);
+    /// ```
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Returns the `Attr` with the given bit representation, or `None` if `bits` is greater than
+    /// `0b111`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Attr;
+    ///
+    /// let all = [Attr::Default, Attr::Bold, Attr::Underline, Attr::BoldUnderline, Attr::Reverse,
+    ///            Attr::BoldReverse, Attr::UnderlineReverse, Attr::BoldReverseUnderline];
+    /// for attr in all.iter() {
+    ///     assert_eq!(Attr::from_bits(attr.bits()), Some(*attr));
+    /// }
+    /// assert_eq!(Attr::from_bits(0b1000), None);
+    /// ```
+    pub fn from_bits(bits: u8) -> Option<Attr> {
+        if bits <= 0b111 {
+            Some(attr_from_bits(bits))
+        } else {
+            None
+        }
+    }
+}
+
+impl BitOr for Attr {
+    type Output = Attr;
+
+    /// Combines two sets of attributes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::Attr;
+    ///
+    /// assert_eq!(Attr::Bold | Attr::Underline, Attr::BoldUnderline);
+    /// assert_eq!(Attr::Reverse | Attr::Default, Attr::Reverse);
+    /// ```
+    fn bitor(self, rhs: Attr) -> Attr {
+        attr_from_bits(self.bits() | rhs.bits())
+    }
+}
+
+/// Returns the `Attr` with the given bit representation, ignoring unknown bits.
+fn attr_from_bits(bits: u8) -> Attr {
+    match bits & 0b111 {
+        0b000 => Attr::Default,
+        0b001 => Attr::Bold,
+        0b010 => Attr::Underline,
+        0b011 => Attr::BoldUnderline,
+        0b100 => Attr::Reverse,
+        0b101 => Attr::BoldReverse,
+        0b110 => Attr::UnderlineReverse,
+        _ => Attr::BoldReverseUnderline,
+    }
+}
+
+/// The set of box-drawing characters used to draw lines and borders.
+///
+/// # Examples
+///
+/// ```
+/// use rustty::BorderStyle;
+///
+/// let style = BorderStyle::Rounded;
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BorderStyle {
+    /// Thin lines: `┌─┐`.
+    Single,
+    /// Double lines: `╔═╗`.
+    Double,
+    /// Thin lines with rounded corners: `╭─╮`.
+    Rounded,
+    /// Thick lines: `┏━┓`.
+    Heavy,
+    /// Thin dashed lines: `┌┄┐`.
+    Dashed,
+}
+
+impl BorderStyle {
+    /// Returns the horizontal line, vertical line and junction characters of the style. Junctions
+    /// are indexed by top/middle/bottom, then left/middle/right.
+    fn glyphs(self) -> (char, char, [[char; 3]; 3]) {
+        match self {
+            BorderStyle::Single => {
+                ('─', '│', [['┌', '┬', '┐'], ['├', '┼', '┤'], ['└', '┴', '┘']])
+            }
+            BorderStyle::Double => {
+                ('═', '║', [['╔', '╦', '╗'], ['╠', '╬', '╣'], ['╚', '╩', '╝']])
+            }
+            BorderStyle::Rounded => {
+                ('─', '│', [['╭', '┬', '╮'], ['├', '┼', '┤'], ['╰', '┴', '╯']])
+            }
+            BorderStyle::Heavy => {
+                ('━', '┃', [['┏', '┳', '┓'], ['┣', '╋', '┫'], ['┗', '┻', '┛']])
+            }
+            BorderStyle::Dashed => {
+                ('┄', '┆', [['┌', '┬', '┐'], ['├', '┼', '┤'], ['└', '┴', '┘']])
+            }
+        }
+    }
+}
+
+// The furthest row or column a cursor position sequence parsed by `CellBuffer::from_ansi` may
+// move to, counting from 1.
+const ANSI_MAX_POSITION: usize = 1024;
+
+// The most cells `CellBuffer::from_ansi` will allocate for its result.
+const ANSI_MAX_CELLS: usize = 1 << 22;
+
+/// An error returned when parsing a string with `CellBuffer::from_ansi` fails.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnsiParseError {
+    /// The input ended in the middle of an escape sequence.
+    Truncated,
+    /// An escape character was followed by the given character rather than `[`.
+    UnsupportedEscape(char),
+    /// An SGR or cursor position sequence contained a parameter that isn't a number, or a cursor
+    /// position beyond the supported range.
+    InvalidParameter,
+    /// The parsed buffer would have more cells than `from_ansi` allows.
+    TooLarge,
+}
+
+impl fmt::Display for AnsiParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AnsiParseError::Truncated => write!(f, "truncated escape sequence"),
+            AnsiParseError::UnsupportedEscape(c) => {
+                write!(f, "unsupported escape sequence ESC {:?}", c)
+            }
+            AnsiParseError::InvalidParameter => write!(f, "invalid escape sequence parameter"),
+            AnsiParseError::TooLarge => write!(f, "parsed buffer is too large"),
+        }
+    }
+}
+
+impl Error for AnsiParseError {}
+
+/// Parses the parameters of a cursor position sequence (the text between `ESC [` and `H`) into a
+/// 0-based `(row, col)`. Missing or zero parameters default to the first row or column, and
+/// positions beyond `ANSI_MAX_POSITION` are rejected.
+fn parse_cursor_position(params: &str) -> Result<(usize, usize), AnsiParseError> {
+    let mut position = [0; 2];
+    for (i, p) in params.split(';').take(2).enumerate() {
+        if !p.is_empty() {
+            let n: usize = p.parse().map_err(|_| AnsiParseError::InvalidParameter)?;
+            if n > ANSI_MAX_POSITION {
+                return Err(AnsiParseError::InvalidParameter);
+            }
+            position[i] = n.saturating_sub(1);
+        }
+    }
+    Ok((position[0], position[1]))
+}
+
+/// Applies the SGR parameters `params` (the text between `ESC [` and `m`) to the style of `cell`.
+///
+/// Unrecognized codes are ignored.
+fn apply_sgr(cell: &mut Cell, params: &str) -> Result<(), AnsiParseError> {
+    let mut codes = Vec::new();
+    for p in params.split(';') {
+        if p.is_empty() {
+            codes.push(0);
+        } else {
+            codes.push(p.parse::<u16>().map_err(|_| AnsiParseError::InvalidParameter)?);
+        }
+    }
+
+    let mut codes = codes.into_iter();
+    while let Some(code) = codes.next() {
+        let attrs = cell.attrs().bits();
+        match code {
+            0 => *cell = Cell::new(cell.ch(), Color::Default, Color::Default, Attr::Default),
+            1 => {
+                cell.set_attrs(attr_from_bits(attrs | Attr::Bold.bits()));
+            }
+            4 => {
+                cell.set_attrs(attr_from_bits(attrs | Attr::Underline.bits()));
+            }
+            7 => {
+                cell.set_attrs(attr_from_bits(attrs | Attr::Reverse.bits()));
+            }
+            22 => {
+                cell.set_attrs(attr_from_bits(attrs & !Attr::Bold.bits()));
+            }
+            24 => {
+                cell.set_attrs(attr_from_bits(attrs & !Attr::Underline.bits()));
+            }
+            27 => {
+                cell.set_attrs(attr_from_bits(attrs & !Attr::Reverse.bits()));
+            }
+            38 => {
+                cell.set_fg(extended_color(&mut codes));
+            }
+            48 => {
+                cell.set_bg(extended_color(&mut codes));
+            }
+            _ => {
+                if let Some(fg) = Color::from_ansi_fg(code) {
+                    cell.set_fg(fg);
+                } else if let Some(bg) = Color::from_ansi_bg(code) {
+                    cell.set_bg(bg);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the SGR sequence that resets the terminal style and then sets the style of `cell`.
+fn sgr_sequence(cell: &Cell) -> String {
+    let mut seq = String::from("\x1b[0");
+    let attrs = cell.attrs().bits();
+    for &(bit, code) in &[(Attr::Bold, "1"), (Attr::Underline, "4"), (Attr::Reverse, "7")] {
+        if attrs & bit.bits() != 0 {
+            seq.push(';');
+            seq.push_str(code);
+        }
+    }
+    for &(color, base) in &[(cell.fg(), 30), (cell.bg(), 40)] {
+        match color {
+            Color::Default => {}
+            Color::Rgb(r, g, b) => seq.push_str(&format!(";{};2;{};{};{}", base + 8, r, g, b)),
+            Color::Byte(n) => seq.push_str(&format!(";{};5;{}", base + 8, n)),
+            c => seq.push_str(&format!(";{}", base + c.as_byte() as u16)),
+        }
+    }
+    seq.push('m');
+    seq
+}
+
+/// Returns the inline CSS for the style of `cell`, or an empty string for the default style.
+fn html_style(cell: &Cell) -> String {
+    let attrs = cell.attrs().bits();
+    let (fg, bg) = if attrs & Attr::Reverse.bits() != 0 {
+        (cell.bg(), cell.fg())
+    } else {
+        (cell.fg(), cell.bg())
+    };
+    let mut props = Vec::new();
+    if let Some((r, g, b)) = fg.to_rgb() {
+        props.push(format!("color:#{:02x}{:02x}{:02x}", r, g, b));
+    }
+    if let Some((r, g, b)) = bg.to_rgb() {
+        props.push(format!("background:#{:02x}{:02x}{:02x}", r, g, b));
+    }
+    if attrs & Attr::Bold.bits() != 0 {
+        props.push(String::from("font-weight:bold"));
+    }
+    if attrs & Attr::Underline.bits() != 0 {
+        props.push(String::from("text-decoration:underline"));
+    }
+    props.join(";")
+}
+
+/// Returns the named `Color` for the basic color index `n`, 0x00..0x07.
+fn basic_color(n: u8) -> Color {
+    match n {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+/// Reads the color following an extended color code (38 or 48): either `5;n` for an 8-bit color
+/// or `2;r;g;b` for an RGB color. Malformed colors resolve to `Color::Default`.
+fn extended_color<I: Iterator<Item = u16>>(codes: &mut I) -> Color {
+    match codes.next() {
+        Some(5) => codes.next().map_or(Color::Default, |n| Color::Byte(n as u8)),
+        Some(2) => {
+            match (codes.next(), codes.next(), codes.next()) {
+                (Some(r), Some(g), Some(b)) => Color::Rgb(r as u8, g as u8, b as u8),
+                _ => Color::Default,
+            }
+        }
+        _ => Color::Default,
+    }
+}
+
+/// An error returned when parsing a string with `Color::parse` fails, holding the rejected input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorParseError(pub String);
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid color {:?}", self.0)
+    }
+}
+
+impl Error for ColorParseError {}
+
+// Rectangle helpers for `CellBuffer::dirty_rects`, which works on (x0, y0, x1, y1) rectangles
+// with exclusive ends.
+
+fn rect_area(r: (usize, usize, usize, usize)) -> usize {
+    (r.2 - r.0) * (r.3 - r.1)
+}
+
+fn rect_union(a: (usize, usize, usize, usize),
+              b: (usize, usize, usize, usize))
+              -> (usize, usize, usize, usize) {
+    (cmp::min(a.0, b.0), cmp::min(a.1, b.1), cmp::max(a.2, b.2), cmp::max(a.3, b.3))
+}
+
+/// Merges rectangles that overlap or touch, including at a corner, until none do.
+fn merge_touching_rects(rects: &mut Vec<(usize, usize, usize, usize)>) {
+    let mut i = 0;
+    while i < rects.len() {
+        let touching = (i + 1..rects.len()).find(|&j| {
+            let (a, b) = (rects[i], rects[j]);
+            a.0 <= b.2 && b.0 <= a.2 && a.1 <= b.3 && b.1 <= a.3
+        });
+        match touching {
+            Some(j) => {
+                rects[i] = rect_union(rects[i], rects[j]);
+                rects.remove(j);
+                // The grown rectangle may now touch ones already checked.
+                i = 0;
+            }
+            None => i += 1,
+        }
+    }
+}
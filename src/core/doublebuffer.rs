@@ -0,0 +1,118 @@
+use std::mem;
+
+use core::cellbuffer::{Cell, CellBuffer};
+use core::position::{Size, HasSize};
+
+/// A pair of equally sized `CellBuffer`s for double buffering.
+///
+/// The front buffer holds the frame that was displayed last and the back buffer the one being
+/// drawn. Once the back buffer is drawn, `swap` makes it the front buffer, and `diff` then gives
+/// the cells that changed from the previous frame, which is now in the back buffer.
+///
+/// # Examples
+///
+/// ```
+/// use rustty::{DoubleBuffer, Cell, Color, Attr};
+///
+/// let mut buffers = DoubleBuffer::new(4, 2, Cell::default());
+/// buffers.back_mut()[(1, 0)] = Cell::new('a', Color::Red, Color::Default, Attr::Default);
+/// buffers.swap();
+///
+/// assert_eq!(buffers.diff(), vec![(1, 0, Cell::new('a', Color::Red, Color::Default,
+///                                                  Attr::Default))]);
+/// assert_eq!(buffers.front()[(1, 0)].ch(), 'a');
+/// ```
+#[derive(Debug, Clone)]
+pub struct DoubleBuffer {
+    front: CellBuffer,
+    back: CellBuffer,
+}
+
+impl DoubleBuffer {
+    /// Constructs a new `DoubleBuffer` with the given number of columns and rows, filling both
+    /// buffers with `blank`.
+    pub fn new(cols: usize, rows: usize, blank: Cell) -> DoubleBuffer {
+        DoubleBuffer {
+            front: CellBuffer::new(cols, rows, blank),
+            back: CellBuffer::new(cols, rows, blank),
+        }
+    }
+
+    /// Returns a reference to the front buffer.
+    pub fn front(&self) -> &CellBuffer {
+        &self.front
+    }
+
+    /// Returns a reference to the back buffer.
+    pub fn back(&self) -> &CellBuffer {
+        &self.back
+    }
+
+    /// Returns a mutable reference to the back buffer, for drawing the next frame.
+    ///
+    /// Use `resize` rather than resizing the back buffer through this reference, so both buffers
+    /// stay the same size.
+    pub fn back_mut(&mut self) -> &mut CellBuffer {
+        &mut self.back
+    }
+
+    /// Exchanges the front and back buffers.
+    pub fn swap(&mut self) {
+        mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Returns the position and contents of every cell of the front buffer that differs from the
+    /// back buffer, in row-major order.
+    ///
+    /// If the buffers are different sizes, such as after resizing the back buffer through
+    /// `back_mut`, every cell of the front buffer is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{DoubleBuffer, Cell, HasSize};
+    ///
+    /// let mut buffers = DoubleBuffer::new(4, 2, Cell::default());
+    /// buffers.back_mut().resize(3, 3, Cell::default());
+    /// buffers.swap();
+    ///
+    /// assert_eq!(buffers.size(), (3, 3));
+    /// assert_eq!(buffers.diff().len(), 9);
+    /// ```
+    pub fn diff(&self) -> Vec<(usize, usize, Cell)> {
+        if self.front.size() != self.back.size() {
+            let (cols, _) = self.front.size();
+            return self.front
+                .iter()
+                .enumerate()
+                .map(|(i, &cell)| (i % cols, i / cols, cell))
+                .collect();
+        }
+        self.front.diff_iter(&self.back).map(|(x, y, &cell)| (x, y, cell)).collect()
+    }
+
+    /// Resizes both buffers to the given number of columns and rows, using `blank` for any new
+    /// cells.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustty::{DoubleBuffer, Cell, HasSize};
+    ///
+    /// let mut buffers = DoubleBuffer::new(4, 2, Cell::default());
+    /// buffers.resize(10, 5, Cell::default());
+    ///
+    /// assert_eq!(buffers.size(), (10, 5));
+    /// assert_eq!(buffers.front().size(), buffers.back().size());
+    /// ```
+    pub fn resize(&mut self, cols: usize, rows: usize, blank: Cell) {
+        self.front.resize(cols, rows, blank);
+        self.back.resize(cols, rows, blank);
+    }
+}
+
+impl HasSize for DoubleBuffer {
+    fn size(&self) -> Size {
+        self.front.size()
+    }
+}
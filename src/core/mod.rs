@@ -1,5 +1,6 @@
 pub mod terminal;
 pub mod cellbuffer;
+pub mod doublebuffer;
 pub mod driver;
 pub mod position;
 pub mod input;
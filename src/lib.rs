@@ -16,11 +16,14 @@
 extern crate term;
 extern crate libc;
 extern crate gag;
+extern crate unicode_width;
 
 mod core;
 pub mod ui;
 
 pub use core::terminal::Terminal;
-pub use core::cellbuffer::{Cell, Color, Attr, CellAccessor};
+pub use core::cellbuffer::{Cell, CellPatch, Color, Attr, CellAccessor, CellBuffer,
+                           BorderStyle, AnsiParseError, ColorParseError};
+pub use core::doublebuffer::DoubleBuffer;
 pub use core::position::{Pos, Size, HasSize, HasPosition};
 pub use core::input::Event;